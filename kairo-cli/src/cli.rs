@@ -1,9 +1,50 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use console::style;
 use dialoguer::{Select, theme::ColorfulTheme};
-use kairo_core::{Result, Url, UrlHandlerApp};
+use kairo_core::{
+    ActivationToken,
+    AppAction,
+    Choice,
+    ChoiceKey,
+    ChoiceStore,
+    Config,
+    Error,
+    ExcludedEntry,
+    HandlerRegistry,
+    LaunchOptions,
+    MimeApps,
+    Result,
+    Selection,
+    Terminal,
+    Url,
+    UrlBatches,
+    UrlCleaner,
+    UrlHandlerApp,
+    Visibility,
+    exec,
+    unwrap_redirects,
+};
+use serde::Serialize;
+
+/// An app, with one of its actions or its main command.
+type Target<'a> = (&'a UrlHandlerApp, Option<&'a AppAction>);
+
+/// The size of the icons resolved by `kairo list`.
+const ICON_SIZE: u16 = 48;
+
+/// The columns printed by `kairo list --format tsv`, in order and as named in the JSON output.
+const TSV_COLUMNS: &[&str] = &[
+    "appid",
+    "name",
+    "comment",
+    "icon",
+    "icon_path",
+    "path",
+    "is_default",
+    "actions",
+];
 
 /// Kairo
 #[derive(Parser, Debug)]
@@ -13,10 +54,19 @@ pub struct Cli {
     #[command(subcommand)]
     command: Commands,
 
+    /// Path to the configuration file. Defaults to $XDG_CONFIG_HOME/kairo/config.toml.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
     /// Paths to search for desktop entries.
     #[arg(long, default_value = None, global = true)]
     search_paths: Option<Vec<std::path::PathBuf>>,
 
+    /// Terminal command used to run terminal applications, with a {command} placeholder (e.g.
+    /// "kitty --single-instance {command}"). Detected automatically if not set.
+    #[arg(long, global = true)]
+    terminal: Option<String>,
+
     #[command(flatten)]
     verbose: clap_verbosity::Verbosity<clap_verbosity::WarnLevel>,
 }
@@ -31,7 +81,24 @@ impl Cli {
             .filter_level(self.verbose.log_level_filter())
             .init();
 
-        self.command.process()
+        let mut config = Config::load_from(self.config.as_deref())?;
+        if self.search_paths.is_some() {
+            config.search_paths = self.search_paths.clone();
+        }
+
+        let options = LaunchOptions {
+            terminal: self
+                .terminal
+                .as_deref()
+                .or(config.terminal.as_deref())
+                .map(Terminal::from_template)
+                .transpose()?,
+            activation_token: ActivationToken::from_env(),
+            wrapper: config.wrapper.clone(),
+            ..Default::default()
+        };
+
+        self.command.process(&config, &options)
     }
 }
 
@@ -57,82 +124,699 @@ enum Commands {
         /// The URL scheme to query (conflicts with --url).
         #[arg(short, long, conflicts_with("url"), required_unless_present("url"))]
         scheme: Option<String>,
+
+        /// Also lists the applications that were left out, and why.
+        #[arg(long, default_value = "false")]
+        excluded: bool,
+
+        /// How to print the applications.
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
     },
 
-    /// Opens the given URL with one of its associated applications.
+    /// Opens the given URLs with one of their associated applications.
+    ///
+    /// URLs opened with the same application are passed to it at once when it accepts several.
     Open {
-        /// The URL to open.
-        url: Url,
+        /// The URLs to open.
+        #[arg(required = true)]
+        urls: Vec<Url>,
 
-        /// Opens the URL using the default or last application used without prompting.
+        /// Opens the URLs using the default or last application used without prompting.
         #[arg(long, default_value = "false")]
         no_prompt: bool,
+
+        /// Always opens URLs from the same host with the selected application.
+        #[arg(long, default_value = "false")]
+        remember: bool,
+
+        /// Opens the URLs as is, without removing tracking parameters.
+        #[arg(long, default_value = "false")]
+        no_clean: bool,
+
+        /// Waits for the application to exit instead of detaching it.
+        #[arg(long, default_value = "false")]
+        wait: bool,
+    },
+
+    /// Shows how a URL would be opened, step by step, without opening it.
+    ///
+    /// Prints the redirectors unwrapped, the tracking parameters removed, the handlers found and
+    /// left out, the routing rule or remembered choice used and the command that would run.
+    Explain {
+        /// The URL to explain.
+        url: Url,
+
+        /// Explains the URL as is, without removing tracking parameters.
+        #[arg(long, default_value = "false")]
+        no_clean: bool,
+    },
+
+    /// Manages the applications remembered for hosts and schemes.
+    Choices {
+        #[command(subcommand)]
+        command: ChoicesCommands,
     },
 }
 
+/// Output formats of `kairo list`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ListFormat {
+    /// Aligned columns with colours, to be read by people.
+    Table,
+    /// A JSON object with the `handlers` and the `excluded` entries.
+    Json,
+    /// Tab-separated values, one application per line after a header row, without the excluded
+    /// entries. The columns are appid, name, comment, icon, icon_path, path, is_default and
+    /// actions, with the action IDs separated by commas.
+    Tsv,
+}
+
+/// The output of `kairo list --format json`.
+#[derive(Serialize)]
+struct ListOutput<'a> {
+    handlers: Vec<AppRecord<'a>>,
+    excluded: &'a [ExcludedEntry],
+}
+
+/// An app printed by `kairo list`, with its icon resolved from the icon theme.
+#[derive(Serialize)]
+struct AppRecord<'a> {
+    #[serde(flatten)]
+    app: &'a UrlHandlerApp,
+    icon_path: Option<PathBuf>,
+}
+
+impl<'a> AppRecord<'a> {
+    fn new(app: &'a UrlHandlerApp) -> Self {
+        Self {
+            app,
+            icon_path: app.icon_path(ICON_SIZE),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum ChoicesCommands {
+    /// Lists all remembered choices.
+    List,
+
+    /// Remembers an application for a host or scheme.
+    Set {
+        #[command(flatten)]
+        target: ChoiceTarget,
+
+        /// The ID of the application to remember.
+        appid: String,
+
+        /// The ID of the application's desktop action to remember.
+        #[arg(long)]
+        action: Option<String>,
+
+        /// Opens matching URLs without prompting, instead of only preselecting the application.
+        #[arg(long, default_value = "false")]
+        always: bool,
+    },
+
+    /// Forgets the remembered choice for a host or scheme.
+    Forget {
+        #[command(flatten)]
+        target: ChoiceTarget,
+    },
+
+    /// Forgets all remembered choices.
+    Clear,
+}
+
+#[derive(Args, Debug)]
+#[group(required = true, multiple = false)]
+struct ChoiceTarget {
+    /// The URL host the choice applies to (conflicts with --scheme).
+    #[arg(long)]
+    host: Option<String>,
+
+    /// The URL scheme the choice applies to (conflicts with --host).
+    #[arg(long)]
+    scheme: Option<String>,
+}
+
+impl ChoiceTarget {
+    fn key(&self) -> ChoiceKey {
+        match (&self.host, &self.scheme) {
+            (Some(host), _) => ChoiceKey::Host(host.to_ascii_lowercase()),
+            (_, Some(scheme)) => ChoiceKey::Scheme(scheme.to_ascii_lowercase()),
+            _ => unreachable!(),
+        }
+    }
+}
+
 impl Commands {
-    fn process(&self) -> Result<()> {
+    fn process(&self, config: &Config, options: &LaunchOptions) -> Result<()> {
         match self {
-            Commands::List { url, scheme } => Self::list(url.clone(), scheme.clone(), None),
-            Commands::Open { url, no_prompt } => Self::open(url.clone(), None, *no_prompt),
+            Commands::List {
+                url,
+                scheme,
+                excluded,
+                format,
+            } => Self::list(url.clone(), scheme.clone(), config, *excluded, *format),
+            Commands::Open {
+                urls,
+                no_prompt,
+                remember,
+                no_clean,
+                wait,
+            } => Self::open(
+                urls.clone(),
+                config,
+                &LaunchOptions {
+                    detach: !wait,
+                    ..options.clone()
+                },
+                *no_prompt || config.picker.no_prompt,
+                *remember,
+                *no_clean,
+            ),
+            Commands::Explain { url, no_clean } => {
+                Self::explain(url.clone(), config, options, *no_clean)
+            }
+            Commands::Choices { command } => command.process(config),
         }
     }
 
     fn list(
         url: Option<Url>,
         scheme: Option<String>,
-        search_paths: Option<Vec<PathBuf>>,
+        config: &Config,
+        excluded: bool,
+        format: ListFormat,
     ) -> Result<()> {
         let scheme = match (url, scheme) {
             (Some(url), _) => url.scheme().to_string(),
             (_, Some(scheme)) => scheme,
             _ => unreachable!(),
         };
-        let apps = UrlHandlerApp::handlers_for_scheme(&scheme, None, search_paths)?;
+        let apps = match HandlerRegistry::from_config(config).handlers_for_scheme(&scheme) {
+            Ok(apps) => apps,
+            Err(Error::NoHandlersFound(_)) if excluded => vec![],
+            Err(e) => return Err(e),
+        };
+        let excluded = match excluded {
+            true => {
+                UrlHandlerApp::scan_scheme(
+                    &scheme,
+                    config.locales.clone(),
+                    config.search_paths.clone(),
+                    &MimeApps::load(),
+                    &Visibility::from_env(),
+                )?
+                .excluded
+            }
+            false => vec![],
+        };
+        if apps.is_empty() && excluded.is_empty() {
+            return Err(Error::NoHandlersFound(scheme));
+        }
+
+        match format {
+            ListFormat::Table => Self::print_table(&apps, &excluded),
+            ListFormat::Json => {
+                let output = ListOutput {
+                    handlers: apps.iter().map(AppRecord::new).collect(),
+                    excluded: &excluded,
+                };
+                println!("{}", serde_json::to_string_pretty(&output)?);
+            }
+            ListFormat::Tsv => {
+                if !excluded.is_empty() {
+                    log::warn!("Excluded entries are not listed in the TSV format");
+                }
+                println!("{}", TSV_COLUMNS.join("\t"));
+                for app in &apps {
+                    println!(
+                        "{}",
+                        Self::tsv_row(&serde_json::to_value(AppRecord::new(app))?)
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn print_table(apps: &[UrlHandlerApp], excluded: &[ExcludedEntry]) {
+        // Fit the longest app ID, so the names stay aligned
+        let width = apps
+            .iter()
+            .map(|app| app.appid.chars().count())
+            .chain(excluded.iter().map(|entry| entry.appid.chars().count()))
+            .max()
+            .unwrap_or_default()
+            .max(16);
 
         println!(
-            "{: <16} {}",
+            "{: <width$} {}",
             style("App ID").bold().green(),
             style("Name").bold().green()
         );
 
         for app in apps {
-            println!("{:<16} {}", app.appid, app.name);
+            match app.is_default {
+                true => println!(
+                    "{:<width$} {} {}",
+                    app.appid,
+                    app.name,
+                    style("(default)").dim()
+                ),
+                false => println!("{:<width$} {}", app.appid, app.name),
+            }
+        }
+
+        if !excluded.is_empty() {
+            println!(
+                "\n{: <width$} {}",
+                style("Excluded").bold().yellow(),
+                style("Reason").bold().yellow()
+            );
+
+            for entry in excluded {
+                println!("{:<width$} {}", entry.appid, style(&entry.reason).dim());
+            }
+        }
+    }
+
+    /// Formats the serialized app as a line of tab-separated values, with the IDs of its actions
+    /// separated by commas.
+    fn tsv_row(app: &serde_json::Value) -> String {
+        TSV_COLUMNS
+            .iter()
+            .map(|column| match &app[column] {
+                serde_json::Value::Null => String::new(),
+                serde_json::Value::String(value) => value.replace(['\t', '\n'], " "),
+                serde_json::Value::Array(actions) => actions
+                    .iter()
+                    .filter_map(|action| action["id"].as_str())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                value => value.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\t")
+    }
+
+    fn open(
+        urls: Vec<Url>,
+        config: &Config,
+        options: &LaunchOptions,
+        no_prompt: bool,
+        remember: bool,
+        no_clean: bool,
+    ) -> Result<()> {
+        let cleaner = match no_clean {
+            true => None,
+            false => Some(config.cleaner()?),
+        };
+        let router = config.router()?;
+        let registry = HandlerRegistry::from_config(config);
+        let mut store = config.choices()?;
+        let mut handlers = HashMap::new();
+        let mut batches = UrlBatches::new();
+        let mut remembered = false;
+        let show_url = urls.len() > 1;
+
+        for url in urls {
+            let url = Self::prepare_url(url, cleaner.as_ref());
+
+            let scheme = url.scheme().to_string();
+            if !handlers.contains_key(&scheme) {
+                let apps = registry.handlers_for_scheme(&scheme)?;
+                handlers.insert(scheme.clone(), apps);
+            }
+            let apps = &handlers[&scheme];
+
+            if let Some(route) = router.route(&url, apps) {
+                batches.push(&route.handler(), route.action, url);
+                continue;
+            }
+
+            let Some(((app, action), prompted)) = Self::select(
+                &url,
+                apps,
+                &store,
+                no_prompt,
+                config.picker.show_actions,
+                show_url,
+            ) else {
+                continue;
+            };
+
+            // Preselect the last used app next time, or skip the prompt if asked to remember it
+            if prompted || remember {
+                let choice = Choice {
+                    handler: app.appid.clone(),
+                    action: action.map(|action| action.id.clone()),
+                    always: remember,
+                };
+                store.remember(ChoiceKey::for_url(&url), choice);
+                remembered = true;
+            }
+
+            batches.push(app, action, url);
+        }
+
+        if remembered {
+            store.save()?;
+        }
+
+        for batch in batches.iter() {
+            println!(
+                "Opening {} with {}...",
+                match batch.urls.len() {
+                    1 => "URL".to_string(),
+                    count => format!("{count} URLs"),
+                },
+                style(batch.app.display_name(batch.action.as_ref()))
+                    .bold()
+                    .green()
+            );
+            batch.open(options)?;
         }
 
         Ok(())
     }
 
-    fn open(url: Url, search_paths: Option<Vec<PathBuf>>, no_prompt: bool) -> Result<()> {
-        let apps = UrlHandlerApp::handlers_for_scheme(url.scheme(), None, search_paths)?;
+    /// Prints every step taken to open the URL, and the commands that would run.
+    fn explain(url: Url, config: &Config, options: &LaunchOptions, no_clean: bool) -> Result<()> {
+        let field = |name: &str| style(format!("{name:<16}")).bold().green();
+        println!("{} {url}", field("URL"));
 
-        if no_prompt || apps.len() == 1 {
-            return Self::open_with_app(&apps[0], url);
+        let unwrapped = unwrap_redirects(&url);
+        match unwrapped.is_modified() {
+            true => println!(
+                "{} {} → {}",
+                field("Unwrapped"),
+                unwrapped.redirectors.join(" → "),
+                unwrapped.url
+            ),
+            false => println!("{} {}", field("Unwrapped"), style("no redirectors").dim()),
         }
+        let url = match no_clean {
+            true => {
+                println!("{} {}", field("Cleaned"), style("skipped").dim());
+                unwrapped.url
+            }
+            false => {
+                let cleaned = config.cleaner()?.clean(&unwrapped.url);
+                match cleaned.is_modified() {
+                    true => println!(
+                        "{} removed {} → {}",
+                        field("Cleaned"),
+                        cleaned.removed.join(", "),
+                        cleaned.url
+                    ),
+                    false => println!(
+                        "{} {}",
+                        field("Cleaned"),
+                        style("no tracking parameters").dim()
+                    ),
+                }
+                cleaned.url
+            }
+        };
 
-        let app_names: Vec<String> = apps
+        let scheme = url.scheme().to_string();
+        let apps = match HandlerRegistry::from_config(config).handlers_for_scheme(&scheme) {
+            Ok(apps) => apps,
+            Err(Error::NoHandlersFound(_)) => vec![],
+            Err(e) => return Err(e),
+        };
+        let excluded = UrlHandlerApp::scan_scheme(
+            &scheme,
+            config.locales.clone(),
+            config.search_paths.clone(),
+            &MimeApps::load(),
+            &Visibility::from_env(),
+        )
+        .map(|scan| scan.excluded)
+        .unwrap_or_default();
+
+        println!(
+            "\n{: <16} {}",
+            style("Handler").bold().green(),
+            style("Status").bold().green()
+        );
+        for app in &apps {
+            match app.is_default {
+                true => println!("{:<16} included {}", app.appid, style("(default)").dim()),
+                false => println!("{:<16} included", app.appid),
+            }
+        }
+        for entry in &excluded {
+            println!(
+                "{:<16} excluded: {}",
+                entry.appid,
+                style(&entry.reason).dim()
+            );
+        }
+        if apps.is_empty() {
+            return Err(Error::NoHandlersFound(scheme));
+        }
+
+        let router = config.router()?;
+        let route = router.route(&url, &apps);
+        let matching = router
+            .rules()
             .iter()
-            .map(|app| format!("{:<16} {}", app.appid, app.name))
-            .collect();
-        let selection = Select::with_theme(&ColorfulTheme::default())
-        .with_prompt("Select an application to open the URL with (press ESC or q to cancel)")
-        .report(false)
-        // TODO: save the last used app as default
-        .default(0)
-        .items(&app_names)
-        .interact_opt()
-        .unwrap();
+            .filter(|rule| rule.matches(&url))
+            .collect::<Vec<_>>();
+        println!(
+            "\n{: <16} {}",
+            style("Rule").bold().green(),
+            style("Status").bold().green()
+        );
+        if matching.is_empty() {
+            println!("{}", style("no rule matches").dim());
+        }
+        for rule in matching {
+            match &route {
+                Some(route) if std::ptr::eq(route.rule, rule) => {
+                    println!("{:<16} used → {}", rule.label(), rule.handler);
+                    break;
+                }
+                _ => {
+                    let reason = match apps.iter().find(|app| app.appid == rule.handler) {
+                        None => format!("handler '{}' is not available", rule.handler),
+                        Some(_) => format!(
+                            "handler '{}' has no action '{}'",
+                            rule.handler,
+                            rule.action.as_deref().unwrap_or_default()
+                        ),
+                    };
+                    println!("{:<16} skipped: {}", rule.label(), style(reason).dim());
+                }
+            }
+        }
 
-        if let Some(selection) = selection {
-            return Self::open_with_app(&apps[selection], url);
+        let store = config.choices()?;
+        match store.lookup(&url) {
+            Some((key, choice)) => println!(
+                "\n{} {} for {key}{}",
+                field("Choice"),
+                choice.handler,
+                match choice.always {
+                    true => " (always)",
+                    false => "",
+                }
+            ),
+            None => println!("\n{} {}", field("Choice"), style("none remembered").dim()),
+        }
+
+        let targets = Self::targets(&apps, config.picker.show_actions);
+        let (app, action, reason) = match &route {
+            Some(route) => (
+                route.handler(),
+                route.action,
+                format!("rule '{}'", route.rule.label()),
+            ),
+            None => {
+                let selection = store.select(&url, &targets, config.picker.no_prompt);
+                let (app, action) = targets[selection.index()];
+                (app.clone(), action, selection.reason().to_string())
+            }
+        };
+        println!(
+            "{} {} {}",
+            field("Selected"),
+            style(app.display_name(action)).bold(),
+            style(format!("({reason})")).dim()
+        );
+
+        let action = action.map(|action| action.id.as_str());
+        if app.uses_dbus(action, options)? {
+            println!(
+                "{} {} {}",
+                field("D-Bus"),
+                app.appid,
+                style("(activated through D-Bus, the command below is the fallback)").dim()
+            );
+        }
+        for (key, value) in &app.env {
+            println!("{} {key}={value}", field("Environment"));
+        }
+        if let Some(dir) = &app.working_dir {
+            println!("{} {}", field("Working dir"), dir.display());
+        }
+        match app.commands(std::slice::from_ref(&url), action, options) {
+            Ok(commands) => {
+                for argv in commands {
+                    let missing = exec::find_program(&argv[0]).is_none();
+                    println!(
+                        "{} {}{}",
+                        field("Command"),
+                        argv.iter()
+                            .map(|arg| shell_quote(arg))
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                        match missing {
+                            true => style(" (not found in PATH)").yellow().to_string(),
+                            false => String::new(),
+                        }
+                    );
+                }
+            }
+            Err(e) => println!("{} {}", field("Command"), style(e).red()),
         }
 
         Ok(())
     }
 
-    fn open_with_app(app: &UrlHandlerApp, url: Url) -> Result<()> {
-        println!("Opening URL with {}...", style(&app.name).bold().green());
-        app.open_url(url)
+    /// Unwraps the redirectors and removes the tracking parameters from the URL, printing what
+    /// changed.
+    fn prepare_url(url: Url, cleaner: Option<&UrlCleaner>) -> Url {
+        let unwrapped = unwrap_redirects(&url);
+        if unwrapped.is_modified() {
+            println!(
+                "Unwrapped {} link",
+                style(unwrapped.redirectors.join(" → ")).dim()
+            );
+        }
+        let url = unwrapped.url;
+
+        match cleaner {
+            Some(cleaner) => {
+                let cleaned = cleaner.clean(&url);
+                if cleaned.is_modified() {
+                    println!("Removed {}", style(cleaned.removed.join(", ")).dim());
+                }
+                cleaned.url
+            }
+            None => url,
+        }
+    }
+
+    /// Picks the app to open the URL with, from the remembered choice or by prompting the user.
+    /// Returns whether the user was prompted, or `None` if the prompt was cancelled.
+    fn select<'a>(
+        url: &Url,
+        apps: &'a [UrlHandlerApp],
+        store: &ChoiceStore,
+        no_prompt: bool,
+        show_actions: bool,
+        show_url: bool,
+    ) -> Option<(Target<'a>, bool)> {
+        let targets = Self::targets(apps, show_actions);
+
+        let (selection, prompted) = match store.select(url, &targets, no_prompt) {
+            Selection::Prompt(preselected) => {
+                let target_names: Vec<String> = targets
+                    .iter()
+                    .map(|(app, action)| format!("{:<16} {}", app.appid, app.display_name(*action)))
+                    .collect();
+                let prompt = match show_url {
+                    true => format!("Select an application to open {url} with"),
+                    false => "Select an application to open the URL with".to_string(),
+                };
+                let selection = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!("{prompt} (press ESC or q to cancel)"))
+                    .report(false)
+                    .default(preselected)
+                    .items(&target_names)
+                    .interact_opt()
+                    .unwrap();
+
+                (selection, true)
+            }
+            selection => (Some(selection.index()), false),
+        };
+
+        selection.map(|index| (targets[index], prompted))
+    }
+
+    /// Every app can be opened with its main command or one of its actions.
+    fn targets(apps: &[UrlHandlerApp], show_actions: bool) -> Vec<Target<'_>> {
+        apps.iter()
+            .flat_map(|app| app.launch_targets().map(move |action| (app, action)))
+            .filter(|(_, action)| show_actions || action.is_none())
+            .collect()
+    }
+}
+
+impl ChoicesCommands {
+    fn process(&self, config: &Config) -> Result<()> {
+        let mut store = config.choices()?;
+
+        match self {
+            ChoicesCommands::List => {
+                println!(
+                    "{: <32} {: <24} {: <24} {}",
+                    style("Host / Scheme").bold().green(),
+                    style("App ID").bold().green(),
+                    style("Action").bold().green(),
+                    style("Always").bold().green()
+                );
+
+                for (key, choice) in store.iter() {
+                    println!(
+                        "{: <32} {: <24} {: <24} {}",
+                        key.to_string(),
+                        choice.handler,
+                        choice.action.as_deref().unwrap_or("-"),
+                        choice.always
+                    );
+                }
+
+                return Ok(());
+            }
+            ChoicesCommands::Set {
+                target,
+                appid,
+                action,
+                always,
+            } => {
+                let choice = Choice {
+                    handler: appid.clone(),
+                    action: action.clone(),
+                    always: *always,
+                };
+                store.remember(target.key(), choice);
+            }
+            ChoicesCommands::Forget { target } => {
+                if store.forget(&target.key()).is_none() {
+                    println!("No choice remembered for {}", target.key());
+                }
+            }
+            ChoicesCommands::Clear => store.clear(),
+        }
+
+        store.save()
+    }
+}
+
+/// Quotes the argument for a POSIX shell, if needed.
+fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+    match !arg.is_empty() && arg.chars().all(safe) {
+        true => arg.to_string(),
+        false => format!("'{}'", arg.replace('\'', r"'\''")),
     }
 }
 
@@ -0,0 +1,245 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{AppAction, Error, Result, UrlHandlerApp};
+
+const CHOICES_FILE: &str = "choices.toml";
+
+/// How the target to open a URL with is picked when no routing rule matches, as an index into
+/// the apps and actions it can be opened with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Selection {
+    /// The remembered choice, opened without prompting because it is always used, prompting is
+    /// disabled or there is a single app.
+    Remembered(usize),
+    /// The first target, opened without prompting because there is no remembered choice and
+    /// prompting is disabled or there is a single app.
+    First,
+    /// The user is prompted, with the remembered choice or the first target preselected.
+    Prompt(usize),
+}
+
+impl Selection {
+    /// The index of the selected or preselected target.
+    pub fn index(self) -> usize {
+        match self {
+            Selection::Remembered(index) | Selection::Prompt(index) => index,
+            Selection::First => 0,
+        }
+    }
+
+    /// Why the target was selected.
+    pub fn reason(self) -> &'static str {
+        match self {
+            Selection::Remembered(_) => "remembered choice",
+            Selection::First => "first handler",
+            Selection::Prompt(_) => "preselected in the picker, which would be shown",
+        }
+    }
+}
+
+/// Identifies what a remembered choice applies to.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChoiceKey {
+    /// Applies to all URLs with the given host.
+    Host(String),
+    /// Applies to all URLs with the given scheme that have no host choice.
+    Scheme(String),
+}
+
+impl ChoiceKey {
+    /// The most specific key for the URL: its host, or its scheme if it has no host.
+    pub fn for_url(url: &Url) -> Self {
+        match url.host_str() {
+            Some(host) => Self::Host(host.to_ascii_lowercase()),
+            None => Self::Scheme(url.scheme().to_ascii_lowercase()),
+        }
+    }
+}
+
+impl fmt::Display for ChoiceKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChoiceKey::Host(host) => write!(f, "{host}"),
+            ChoiceKey::Scheme(scheme) => write!(f, "{scheme}:"),
+        }
+    }
+}
+
+/// A handler chosen by the user for a host or scheme.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Choice {
+    /// The app ID of the chosen handler.
+    pub handler: String,
+
+    /// The ID of the handler's desktop action, if one was chosen.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+
+    /// Whether the handler should be used without prompting. Otherwise it is only preselected.
+    #[serde(default)]
+    pub always: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Choices {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    hosts: BTreeMap<String, Choice>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    schemes: BTreeMap<String, Choice>,
+}
+
+/// Persists the handlers chosen by the user per host and per scheme.
+#[derive(Clone, Debug)]
+pub struct ChoiceStore {
+    path: PathBuf,
+    choices: Choices,
+}
+
+impl ChoiceStore {
+    /// Opens the store at `$XDG_STATE_HOME/kairo/choices.toml`.
+    pub fn open_default() -> Result<Self> {
+        let path = xdg::BaseDirectories::with_prefix("kairo").place_state_file(CHOICES_FILE)?;
+        Self::open(path)
+    }
+
+    /// Opens the store at `choices.toml` in the given directory, or at the default location if
+    /// `None`.
+    pub fn open_from(dir: Option<&Path>) -> Result<Self> {
+        match dir {
+            Some(dir) => Self::open(dir.join(CHOICES_FILE)),
+            None => Self::open_default(),
+        }
+    }
+
+    /// Opens the store at the given path. A missing file is treated as an empty store.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        log::debug!("Loading remembered choices from {path:?}");
+
+        let choices = match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).map_err(|source| Error::LoadChoices {
+                path: path.clone().into(),
+                source,
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Choices::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self { path, choices })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the choices back to disk.
+    pub fn save(&self) -> Result<()> {
+        log::debug!("Saving remembered choices to {:?}", self.path);
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, toml::to_string(&self.choices)?)?;
+
+        Ok(())
+    }
+
+    /// Finds the choice for the URL, preferring its host over its scheme.
+    pub fn lookup(&self, url: &Url) -> Option<(ChoiceKey, &Choice)> {
+        let host = ChoiceKey::for_url(url);
+        let scheme = ChoiceKey::Scheme(url.scheme().to_ascii_lowercase());
+
+        [host, scheme]
+            .into_iter()
+            .find_map(|key| self.get(&key).map(|choice| (key, choice)))
+    }
+
+    /// Picks the target to open the URL with among the apps and actions it can be opened with,
+    /// from the remembered choice or by prompting the user.
+    pub fn select(
+        &self,
+        url: &Url,
+        targets: &[(&UrlHandlerApp, Option<&AppAction>)],
+        no_prompt: bool,
+    ) -> Selection {
+        let remembered = self.lookup(url).and_then(|(_, choice)| {
+            targets
+                .iter()
+                .position(|(app, action)| {
+                    app.appid == choice.handler
+                        && action.map(|action| &action.id) == choice.action.as_ref()
+                })
+                .map(|index| (index, choice.always))
+        });
+        let single_app = targets.iter().all(|(app, _)| {
+            targets
+                .first()
+                .is_some_and(|(first, _)| first.appid == app.appid)
+        });
+
+        match remembered {
+            Some((index, always)) if always || no_prompt || single_app => {
+                Selection::Remembered(index)
+            }
+            None if no_prompt || single_app => Selection::First,
+            remembered => Selection::Prompt(remembered.map(|(index, _)| index).unwrap_or_default()),
+        }
+    }
+
+    pub fn get(&self, key: &ChoiceKey) -> Option<&Choice> {
+        match key {
+            ChoiceKey::Host(host) => self.choices.hosts.get(host),
+            ChoiceKey::Scheme(scheme) => self.choices.schemes.get(scheme),
+        }
+    }
+
+    /// Records a choice, replacing any previous one for the same key.
+    pub fn remember(&mut self, key: ChoiceKey, choice: Choice) {
+        log::info!("Remembering {} for {key}", choice.handler);
+
+        match key {
+            ChoiceKey::Host(host) => self.choices.hosts.insert(host, choice),
+            ChoiceKey::Scheme(scheme) => self.choices.schemes.insert(scheme, choice),
+        };
+    }
+
+    /// Removes the choice for the key, returning it if it existed.
+    pub fn forget(&mut self, key: &ChoiceKey) -> Option<Choice> {
+        log::info!("Forgetting choice for {key}");
+
+        match key {
+            ChoiceKey::Host(host) => self.choices.hosts.remove(host),
+            ChoiceKey::Scheme(scheme) => self.choices.schemes.remove(scheme),
+        }
+    }
+
+    /// Removes all remembered choices.
+    pub fn clear(&mut self) {
+        self.choices = Choices::default();
+    }
+
+    /// Iterates over all remembered choices, hosts first.
+    pub fn iter(&self) -> impl Iterator<Item = (ChoiceKey, &Choice)> {
+        let hosts = self
+            .choices
+            .hosts
+            .iter()
+            .map(|(host, choice)| (ChoiceKey::Host(host.clone()), choice));
+        let schemes = self
+            .choices
+            .schemes
+            .iter()
+            .map(|(scheme, choice)| (ChoiceKey::Scheme(scheme.clone()), choice));
+
+        hosts.chain(schemes)
+    }
+}
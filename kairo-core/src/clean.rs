@@ -0,0 +1,216 @@
+use std::{collections::BTreeMap, path::Path};
+
+use regex::Regex;
+use serde::Deserialize;
+use url::{Url, form_urlencoded};
+
+use crate::{Error, Result};
+
+const BUILTIN_RULES: &str = include_str!("../assets/clearurls.json");
+const RULES_FILE: &str = "clearurls.json";
+
+/// Rule data in the format used by [ClearURLs](https://docs.clearurls.xyz/latest/specs/rules/).
+#[derive(Deserialize)]
+struct RuleData {
+    providers: BTreeMap<String, ProviderData>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProviderData {
+    url_pattern: String,
+    #[serde(default)]
+    rules: Vec<String>,
+    #[serde(default)]
+    raw_rules: Vec<String>,
+    #[serde(default)]
+    exceptions: Vec<String>,
+}
+
+/// A set of cleaning rules that apply to the URLs matching a pattern.
+#[derive(Clone, Debug)]
+struct Provider {
+    name: String,
+    url_pattern: Regex,
+    /// Matches the names of the query parameters to remove.
+    rules: Vec<Regex>,
+    /// Matches parts of the URL to remove.
+    raw_rules: Vec<Regex>,
+    /// Matches URLs the provider should not touch.
+    exceptions: Vec<Regex>,
+}
+
+impl Provider {
+    fn from_data(name: String, data: ProviderData) -> Result<Self> {
+        let compile_all = |patterns: Vec<String>, anchored: bool| {
+            patterns
+                .iter()
+                .map(|pattern| match anchored {
+                    true => Regex::new(&format!("(?i)^(?:{pattern})$")),
+                    false => Regex::new(&format!("(?i){pattern}")),
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()
+        };
+
+        Ok(Self {
+            url_pattern: Regex::new(&format!("(?i){}", data.url_pattern))?,
+            rules: compile_all(data.rules, true)?,
+            raw_rules: compile_all(data.raw_rules, false)?,
+            exceptions: compile_all(data.exceptions, false)?,
+            name,
+        })
+    }
+
+    fn applies_to(&self, url: &Url) -> bool {
+        self.url_pattern.is_match(url.as_str())
+            && !self
+                .exceptions
+                .iter()
+                .any(|exception| exception.is_match(url.as_str()))
+    }
+}
+
+/// The result of cleaning a URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CleanedUrl {
+    /// The URL without the tracking parts.
+    pub url: Url,
+    /// The query parameters (`name=value`) and raw URL parts that were removed.
+    pub removed: Vec<String>,
+}
+
+impl CleanedUrl {
+    /// Checks if anything was removed from the URL.
+    pub fn is_modified(&self) -> bool {
+        !self.removed.is_empty()
+    }
+}
+
+/// Removes tracking parameters such as `utm_*` or `fbclid` from URLs.
+#[derive(Clone, Debug)]
+pub struct UrlCleaner {
+    providers: Vec<Provider>,
+}
+
+impl Default for UrlCleaner {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl UrlCleaner {
+    /// Creates a cleaner with the built-in rules only.
+    pub fn builtin() -> Self {
+        Self::from_json(BUILTIN_RULES).expect("built-in cleaning rules should be valid")
+    }
+
+    /// Creates a cleaner from rules in the ClearURLs JSON format.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let data: RuleData = serde_json::from_str(json)?;
+        let providers = data
+            .providers
+            .into_iter()
+            .map(|(name, data)| Provider::from_data(name, data))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { providers })
+    }
+
+    /// Creates a cleaner with the built-in rules plus the user rules found in
+    /// `$XDG_CONFIG_HOME/kairo/clearurls.json`, if any.
+    pub fn load() -> Result<Self> {
+        let cleaner = Self::builtin();
+
+        match xdg::BaseDirectories::with_prefix("kairo").find_config_file(RULES_FILE) {
+            Some(path) => cleaner.with_rules_file(&path),
+            None => Ok(cleaner),
+        }
+    }
+
+    /// Creates a cleaner with the built-in rules plus the user rules in `clearurls.json` in the
+    /// given directory, or in the default location if `None`.
+    pub fn load_from(dir: Option<&Path>) -> Result<Self> {
+        let Some(dir) = dir else {
+            return Self::load();
+        };
+
+        let path = dir.join(RULES_FILE);
+        match path.is_file() {
+            true => Self::builtin().with_rules_file(&path),
+            false => Ok(Self::builtin()),
+        }
+    }
+
+    /// Adds the rules from a ClearURLs JSON file to this cleaner.
+    pub fn with_rules_file(mut self, path: &Path) -> Result<Self> {
+        log::debug!("Loading URL cleaning rules from {path:?}");
+
+        let json = std::fs::read_to_string(path)?;
+        let user = Self::from_json(&json).map_err(|e| Error::LoadCleaningRules {
+            path: path.into(),
+            reason: e.to_string(),
+        })?;
+        self.providers.extend(user.providers);
+
+        Ok(self)
+    }
+
+    /// Removes the tracking parts of the URL using all the providers that apply to it.
+    pub fn clean(&self, url: &Url) -> CleanedUrl {
+        let mut url = url.clone();
+        let mut removed = vec![];
+
+        for provider in &self.providers {
+            if !provider.applies_to(&url) {
+                continue;
+            }
+
+            for raw_rule in &provider.raw_rules {
+                let raw = url.as_str();
+                let matches = raw_rule
+                    .find_iter(raw)
+                    .map(|m| m.as_str().to_string())
+                    .collect::<Vec<_>>();
+                if matches.is_empty() {
+                    continue;
+                }
+
+                match Url::parse(&raw_rule.replace_all(raw, "")) {
+                    Ok(cleaned) => {
+                        url = cleaned;
+                        removed.extend(matches);
+                    }
+                    Err(e) => log::warn!(
+                        "Ignoring raw rule '{raw_rule}' from provider '{}': {e}",
+                        provider.name
+                    ),
+                }
+            }
+
+            if let Some(query) = url.query() {
+                let (kept, dropped): (Vec<_>, Vec<_>) = query
+                    .split('&')
+                    .filter(|pair| !pair.is_empty())
+                    .partition(|pair| {
+                        let name = form_urlencoded::parse(pair.as_bytes())
+                            .next()
+                            .map(|(name, _)| name)
+                            .unwrap_or_default();
+                        !provider.rules.iter().any(|rule| rule.is_match(&name))
+                    });
+
+                if !dropped.is_empty() {
+                    removed.extend(dropped.iter().map(|pair| pair.to_string()));
+                    let query = kept.join("&");
+                    url.set_query((!query.is_empty()).then_some(&query));
+                }
+            }
+        }
+
+        if !removed.is_empty() {
+            log::info!("Removed tracking parts from URL: {removed:?}");
+        }
+
+        CleanedUrl { url, removed }
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use freedesktop_desktop_entry as fde;
+use serde::Deserialize;
+
+use crate::{
+    ChoiceStore,
+    Error,
+    LaunchWrapper,
+    Result,
+    Router,
+    UrlCleaner,
+    UrlHandlerApp,
+    exec,
+    handler::Launcher,
+};
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// How the application picker behaves.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PickerConfig {
+    /// Opens URLs with the remembered or default application instead of showing the picker.
+    pub no_prompt: bool,
+    /// Lists the desktop actions of each application (e.g. "New Private Window") as separate
+    /// choices.
+    pub show_actions: bool,
+}
+
+impl Default for PickerConfig {
+    fn default() -> Self {
+        Self {
+            no_prompt: false,
+            show_actions: true,
+        }
+    }
+}
+
+/// A handler that runs a command from the configuration instead of a desktop entry.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomHandler {
+    /// The app ID of the handler, used in routing rules and remembered choices. A desktop entry
+    /// with the same ID is replaced by this handler.
+    pub id: String,
+    pub name: String,
+    pub comment: Option<String>,
+    /// An icon name from the icon theme, or the path to an image.
+    pub icon: Option<String>,
+    /// The command to run, with `{url}` replaced by the URL and `{urls}` by all the URLs opened
+    /// at once. Field codes such as `%u` or `%U` can also be used, as in a desktop entry.
+    pub command: String,
+    /// The URL schemes the handler can open (e.g. "https").
+    pub schemes: Vec<String>,
+    /// Whether the command runs in a terminal emulator.
+    #[serde(default)]
+    pub terminal: bool,
+    /// Environment variables set for the command.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// The directory the command runs in.
+    pub working_dir: Option<PathBuf>,
+}
+
+impl CustomHandler {
+    /// Checks if the handler can open URLs with the given scheme.
+    pub fn handles(&self, scheme: &str) -> bool {
+        self.schemes
+            .iter()
+            .any(|handled| handled.eq_ignore_ascii_case(scheme))
+    }
+
+    /// The command as the value of an Exec key, with the placeholders replaced by field codes.
+    ///
+    /// Other `%` characters are escaped, so they are passed as is (e.g. in `yt-dlp -o
+    /// '%(title)s.%(ext)s' {url}`).
+    pub fn exec(&self) -> String {
+        self.command
+            .replace('%', "%%")
+            .replace("{urls}", "%U")
+            .replace("{url}", "%u")
+    }
+
+    /// Creates the app for this handler, defined in the configuration file at the given path.
+    pub fn to_app(&self, path: &Path) -> UrlHandlerApp {
+        UrlHandlerApp {
+            appid: self.id.clone(),
+            name: self.name.clone(),
+            comment: self.comment.clone(),
+            icon: self
+                .icon
+                .as_deref()
+                .map(fde::IconSource::from_unknown)
+                .unwrap_or_default(),
+            path: path.to_path_buf(),
+            actions: vec![],
+            terminal: self.terminal,
+            is_default: false,
+            args: vec![],
+            env: self.env.clone(),
+            working_dir: self.working_dir.clone(),
+            wrapper: None,
+            launcher: Launcher::Command(self.exec()),
+        }
+    }
+
+    fn validate(&self) -> std::result::Result<(), String> {
+        if self.schemes.is_empty() {
+            return Err("no schemes set".to_string());
+        }
+        let exec = self.exec();
+        exec::split(&exec).map_err(|e| e.to_string())?;
+        if !exec::accepts_uris(&exec) {
+            return Err("the command has no {url} or {urls} placeholder".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// How an installed application is launched, overriding its desktop entry.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    /// Environment variables set for the application (e.g. `MOZ_ENABLE_WAYLAND=1`).
+    pub env: BTreeMap<String, String>,
+    /// The directory the application runs in, instead of the `Path` key of its desktop entry.
+    pub working_dir: Option<PathBuf>,
+}
+
+/// The user's configuration, read from `$XDG_CONFIG_HOME/kairo/config.toml`.
+///
+/// Every setting is optional, and the command line flags take precedence over it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Paths to search for desktop entries, instead of the XDG data directories. A leading `~/`
+    /// is replaced by the home directory.
+    pub search_paths: Option<Vec<PathBuf>>,
+    /// Locales used for the names of the applications, instead of `$LANG` and `$LANGUAGES`.
+    pub locales: Option<Vec<String>>,
+    /// Terminal command used to run terminal applications, with a `{command}` placeholder.
+    pub terminal: Option<String>,
+    /// The theme of the desktop picker, by name (e.g. "Tokyo Night" or "dracula").
+    pub theme: Option<String>,
+    /// Command wrapping the launched apps, either a built-in one such as "systemd-run" or a
+    /// template with a `{command}` placeholder.
+    pub wrapper: Option<LaunchWrapper>,
+    pub picker: PickerConfig,
+    /// Handlers that run a command instead of a desktop entry.
+    pub handlers: Vec<CustomHandler>,
+    /// Launch settings of the applications, by app ID.
+    pub apps: BTreeMap<String, AppConfig>,
+    /// The file the configuration was loaded from, if any.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+    /// The directory of a configuration file given explicitly, where the routing rules, URL
+    /// cleaning rules and remembered choices are kept instead of their default locations.
+    #[serde(skip)]
+    pub dir: Option<PathBuf>,
+}
+
+impl Config {
+    /// Loads the configuration from `$XDG_CONFIG_HOME/kairo/config.toml`, or the defaults if
+    /// there is no such file.
+    pub fn load() -> Result<Self> {
+        match xdg::BaseDirectories::with_prefix("kairo").find_config_file(CONFIG_FILE) {
+            Some(path) => Self::from_file(&path),
+            None => {
+                log::debug!("No configuration file found");
+                Ok(Self::default())
+            }
+        }
+    }
+
+    /// Loads the configuration from the given file, or from the default location if `None`.
+    ///
+    /// The other files read by Kairo are then looked up next to the given file, see
+    /// [Config::dir].
+    pub fn load_from(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Ok(Self {
+                dir: Some(path.parent().unwrap_or(Path::new("")).to_path_buf()),
+                ..Self::from_file(path)?
+            }),
+            None => Self::load(),
+        }
+    }
+
+    /// Loads the configuration from a TOML file.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        log::debug!("Loading configuration from {path:?}");
+
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content, path)
+    }
+
+    /// Parses the configuration of the file at the given path, reporting errors with the line and
+    /// column they were found at.
+    ///
+    /// Relative paths are resolved against the directory of the file.
+    pub fn parse(content: &str, path: &Path) -> Result<Self> {
+        let mut config: Self = toml::from_str(content).map_err(|source| Error::LoadConfig {
+            path: path.into(),
+            source,
+        })?;
+        config.path = Some(path.to_path_buf());
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        let resolve = |path: &mut PathBuf| resolve_path(path, base);
+        if let Some(search_paths) = &mut config.search_paths {
+            search_paths.iter_mut().for_each(resolve);
+        }
+        let working_dirs = config
+            .handlers
+            .iter_mut()
+            .filter_map(|handler| handler.working_dir.as_mut())
+            .chain(
+                config
+                    .apps
+                    .values_mut()
+                    .filter_map(|app| app.working_dir.as_mut()),
+            );
+        working_dirs.for_each(resolve);
+
+        for handler in &config.handlers {
+            handler
+                .validate()
+                .map_err(|reason| Error::InvalidCustomHandler {
+                    path: path.into(),
+                    id: handler.id.clone(),
+                    reason,
+                })?;
+        }
+
+        Ok(config)
+    }
+
+    /// Loads the routing rules, from [Config::dir] if set.
+    pub fn router(&self) -> Result<Router> {
+        Router::load_from(self.dir.as_deref())
+    }
+
+    /// Loads the URL cleaning rules, from [Config::dir] if set.
+    pub fn cleaner(&self) -> Result<UrlCleaner> {
+        UrlCleaner::load_from(self.dir.as_deref())
+    }
+
+    /// Opens the remembered choices, in [Config::dir] if set.
+    pub fn choices(&self) -> Result<ChoiceStore> {
+        ChoiceStore::open_from(self.dir.as_deref())
+    }
+
+    /// Creates the apps for the custom handlers of the scheme.
+    pub fn custom_handlers(&self, scheme: &str) -> Vec<UrlHandlerApp> {
+        let path = self.path.clone().unwrap_or_default();
+
+        self.handlers
+            .iter()
+            .filter(|handler| handler.handles(scheme))
+            .map(|handler| handler.to_app(&path))
+            .collect()
+    }
+
+    /// Adds the custom handlers of the scheme after the given apps, replacing the ones with the
+    /// same ID.
+    pub fn with_custom_handlers(
+        &self,
+        scheme: &str,
+        mut apps: Vec<UrlHandlerApp>,
+    ) -> Vec<UrlHandlerApp> {
+        for custom in self.custom_handlers(scheme) {
+            match apps.iter_mut().find(|app| app.appid == custom.appid) {
+                Some(app) => *app = custom,
+                None => apps.push(custom),
+            }
+        }
+
+        apps
+    }
+}
+
+/// Replaces a leading `~` with the home directory, and makes relative paths relative to `base`.
+pub(crate) fn resolve_path(path: &mut PathBuf, base: &Path) {
+    expand_home(path);
+    if path.is_relative() {
+        *path = base.join(&*path);
+    }
+}
+
+/// Replaces a leading `~` with the home directory.
+fn expand_home(path: &mut PathBuf) {
+    let Ok(relative) = path.strip_prefix("~") else {
+        return;
+    };
+
+    if let Some(home) = std::env::var_os("HOME") {
+        *path = PathBuf::from(home).join(relative);
+    }
+}
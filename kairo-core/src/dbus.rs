@@ -0,0 +1,80 @@
+use std::{collections::HashMap, time::Duration};
+
+use zbus::{
+    blocking::{Connection, connection},
+    zvariant::Value,
+};
+
+use crate::{ActivationToken, Result, startup::TokenSource};
+
+const APPLICATION_INTERFACE: &str = "org.freedesktop.Application";
+
+/// How long to wait for an application to answer before falling back to its `Exec` key.
+const METHOD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Converts an app ID to the object path of its `org.freedesktop.Application` interface (e.g.
+/// "org.gnome.Epiphany" → "/org/gnome/Epiphany").
+pub fn object_path(appid: &str) -> String {
+    format!("/{}", appid.replace('.', "/").replace('-', "_"))
+}
+
+/// Opens URIs in applications that set `DBusActivatable=true`, calling
+/// `org.freedesktop.Application.Open` on the bus name matching their app ID.
+///
+/// See the [D-Bus Activation](https://specifications.freedesktop.org/desktop-entry-spec/latest/dbus.html)
+/// section of the Desktop Entry Specification.
+#[derive(Clone, Debug)]
+pub struct DBusActivator {
+    connection: Connection,
+}
+
+impl DBusActivator {
+    /// Connects to the session bus.
+    pub fn session() -> Result<Self> {
+        let connection = connection::Builder::session()?
+            .method_timeout(METHOD_TIMEOUT)
+            .build()?;
+
+        Ok(Self { connection })
+    }
+
+    /// Connects to the bus at the given address (e.g. "unix:path=/run/user/1000/bus").
+    pub fn with_address(address: &str) -> Result<Self> {
+        let connection = connection::Builder::address(address)?
+            .method_timeout(METHOD_TIMEOUT)
+            .build()?;
+
+        Ok(Self { connection })
+    }
+
+    /// Asks the application to open the URIs, starting it through the bus if needed. The
+    /// activation token, if any, lets the application take the focus.
+    pub fn open(&self, appid: &str, uris: &[&str], token: Option<&ActivationToken>) -> Result<()> {
+        let path = object_path(appid);
+        log::debug!("Calling {APPLICATION_INTERFACE}.Open on {appid} at {path}");
+
+        self.connection.call_method(
+            Some(appid),
+            path.as_str(),
+            Some(APPLICATION_INTERFACE),
+            "Open",
+            &(uris, platform_data(token)),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Extra information about the launch passed along with the URIs.
+fn platform_data(token: Option<&ActivationToken>) -> HashMap<&'static str, Value<'_>> {
+    let mut data = HashMap::new();
+    if let Some(token) = token {
+        let key = match token.source() {
+            TokenSource::Wayland => "activation-token",
+            TokenSource::X11 => "desktop-startup-id",
+        };
+        data.insert(key, Value::from(token.as_str()));
+    }
+
+    data
+}
@@ -0,0 +1,48 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Computes the desktop file ID of an entry, relative to the data directory it was found in, as
+/// defined by the [Desktop Entry Specification](https://specifications.freedesktop.org/desktop-entry-spec/latest/file-naming.html#desktop-file-id).
+///
+/// Subdirectories become prefixes separated by `-`, so `applications/kde/konsole.desktop` is
+/// `kde-konsole`. Like the app IDs, the `.desktop` suffix is dropped.
+pub fn desktop_id(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?.to_str()?;
+    let id = relative.strip_suffix(".desktop")?;
+
+    match id.is_empty() {
+        true => None,
+        false => Some(id.replace('/', "-")),
+    }
+}
+
+/// Collects the `.desktop` files under the directory, sorted, skipping directories already
+/// visited to avoid symlink loops.
+pub(crate) fn walk(dir: &Path, visited: &mut BTreeSet<PathBuf>, found: &mut Vec<PathBuf>) {
+    let Ok(canonical) = dir.canonicalize() else {
+        return;
+    };
+    if !visited.insert(canonical) {
+        return;
+    }
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    paths.sort_unstable();
+
+    for path in paths {
+        if path.is_dir() {
+            walk(&path, visited, found);
+        } else if path.is_file() && path.extension().is_some_and(|ext| ext == "desktop") {
+            found.push(path);
+        }
+    }
+}
@@ -6,6 +6,29 @@ pub enum Error {
     #[error("failed to execute application {0}: {1}")]
     OpenUrl(String, std::process::ExitStatus),
 
+    #[error(
+        "{role} '{program}' needed to open {appid} was not found in PATH, install it or {}",
+        .role.remedy(path)
+    )]
+    ProgramNotFound {
+        appid: String,
+        path: Box<std::path::Path>,
+        program: String,
+        role: ProgramRole,
+    },
+
+    #[error("no terminal emulator found to run {0}")]
+    NoTerminalFound(String),
+
+    #[error("invalid terminal command template: '{0}'")]
+    InvalidTerminalTemplate(String),
+
+    #[error("invalid launch wrapper template: '{0}'")]
+    InvalidWrapperTemplate(String),
+
+    #[error("D-Bus error: {0}")]
+    DBus(#[from] zbus::Error),
+
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -18,6 +41,94 @@ pub enum Error {
     #[error("failed to parse Exec command: {0}")]
     ParseExecArgs(#[from] crate::exec::ExecParseError),
 
-    #[error("failed to parse arguments: {0}")]
-    ExecArgsShellParse(#[from] shell_words::ParseError),
+    #[error("failed to load configuration from {path}: {source}")]
+    LoadConfig {
+        path: Box<std::path::Path>,
+        source: toml::de::Error,
+    },
+
+    #[error("invalid handler '{id}' in {path}: {reason}")]
+    InvalidCustomHandler {
+        path: Box<std::path::Path>,
+        id: String,
+        reason: String,
+    },
+
+    #[error("failed to load routing rules from {path}: {source}")]
+    LoadRules {
+        path: Box<std::path::Path>,
+        source: toml::de::Error,
+    },
+
+    #[error("failed to load remembered choices from {path}: {source}")]
+    LoadChoices {
+        path: Box<std::path::Path>,
+        source: toml::de::Error,
+    },
+
+    #[error("failed to serialize TOML: {0}")]
+    TomlSerialize(#[from] toml::ser::Error),
+
+    #[error("failed to read or write JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("invalid regular expression: {0}")]
+    Regex(#[from] regex::Error),
+
+    #[error("failed to load URL cleaning rules from {path}: {reason}")]
+    LoadCleaningRules {
+        path: Box<std::path::Path>,
+        reason: String,
+    },
+}
+
+/// What a program run to open URLs is for, to tell the user where it comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgramRole {
+    /// The command of the application, from its desktop entry or custom handler.
+    App,
+    /// The terminal emulator running a terminal application.
+    Terminal,
+    /// The launch wrapper the command is run through.
+    Wrapper,
+}
+
+impl ProgramRole {
+    /// How the user can stop relying on the program, besides installing it.
+    fn remedy(&self, path: &std::path::Path) -> String {
+        match self {
+            ProgramRole::App => format!("update {}", path.display()),
+            ProgramRole::Terminal => {
+                "choose another terminal with `terminal` in config.toml or --terminal".to_string()
+            }
+            ProgramRole::Wrapper => {
+                "change the `wrapper` set in config.toml or rules.toml".to_string()
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ProgramRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramRole::App => write!(f, "command"),
+            ProgramRole::Terminal => write!(f, "terminal"),
+            ProgramRole::Wrapper => write!(f, "launch wrapper"),
+        }
+    }
+}
+
+/// The exit code used when the command of a handler is not installed, as in shells.
+pub const EXIT_PROGRAM_NOT_FOUND: i32 = 127;
+
+impl Error {
+    /// The exit code the binaries should return for this error: the one of the handler if it
+    /// failed, [EXIT_PROGRAM_NOT_FOUND] if its command is missing, or 1 otherwise.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::OpenUrl(_, status) => status.code().unwrap_or(1),
+            Error::ProgramNotFound { .. } => EXIT_PROGRAM_NOT_FOUND,
+            _ => 1,
+        }
+    }
 }
@@ -1,4 +1,11 @@
+use std::{
+    ffi::OsStr,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
 use freedesktop_desktop_entry as fde;
+use url::Url;
 
 use crate::Result;
 
@@ -15,6 +22,12 @@ pub enum ExecParseError {
 
     #[error("Exec key was not found in {path}")]
     ExecFieldNotFound { path: Box<std::path::Path> },
+
+    #[error("Exec key was not found for action '{action}' in {path}")]
+    ActionExecFieldNotFound {
+        action: String,
+        path: Box<std::path::Path>,
+    },
 }
 
 pub struct ExecParser<'a, L>
@@ -23,6 +36,7 @@ where
 {
     de: &'a fde::DesktopEntry,
     locales: &'a [L],
+    args: &'a [String],
 }
 
 impl<'a, L> ExecParser<'a, L>
@@ -33,7 +47,18 @@ where
     where
         L: AsRef<str>,
     {
-        ExecParser { de, locales }
+        ExecParser {
+            de,
+            locales,
+            args: &[],
+        }
+    }
+
+    /// Passes extra arguments to the application (e.g. a browser profile), right before the ones
+    /// receiving the URLs. See [insert_args].
+    pub fn with_args(mut self, args: &'a [String]) -> Self {
+        self.args = args;
+        self
     }
 
     pub fn parse_with_uris(&self, uris: &[&str]) -> Result<(String, Vec<String>)> {
@@ -41,22 +66,41 @@ where
             path: self.de.path.clone().into(),
         })?;
 
-        let exec = if let Some(without_prefix) = exec.strip_prefix('\"') {
-            without_prefix
-                .strip_suffix('\"')
-                .ok_or(ExecParseError::InvalidFormat {
-                    reason: "unmatched quote".into(),
-                    path: self.de.path.clone().into(),
-                })?
-        } else {
-            exec
+        self.parse_exec(exec, uris)
+    }
+
+    /// Parses the Exec key of a `[Desktop Action <action>]` group.
+    pub fn parse_action_with_uris(
+        &self,
+        action: &str,
+        uris: &[&str],
+    ) -> Result<(String, Vec<String>)> {
+        let exec = self
+            .de
+            .action_exec(action)
+            .ok_or(ExecParseError::ActionExecFieldNotFound {
+                action: action.to_string(),
+                path: self.de.path.clone().into(),
+            })?;
+
+        self.parse_exec(exec, uris)
+    }
+
+    fn parse_exec(&self, exec: &str, uris: &[&str]) -> Result<(String, Vec<String>)> {
+        let name = self.de.name(self.locales);
+        let location = self.de.path.to_string_lossy();
+        let fields = FieldValues {
+            icon: self.de.icon(),
+            name: name.as_deref(),
+            location: Some(&location),
         };
 
-        let exec_args = shell_words::split(exec)?
-            .iter()
-            .flat_map(|arg| self.parse_arg(arg, uris))
-            .flatten()
-            .collect::<Vec<_>>();
+        let exec_args = expand_with_args(exec, self.args, uris, &fields).map_err(|e| {
+            ExecParseError::InvalidFormat {
+                reason: e.to_string(),
+                path: self.de.path.clone().into(),
+            }
+        })?;
 
         match exec_args.as_slice() {
             [cmd, args @ ..] => Ok((cmd.to_string(), args.to_vec())),
@@ -65,75 +109,340 @@ where
             })?,
         }
     }
+}
 
-    fn parse_arg(&self, arg: &str, uris: &[&str]) -> Option<Vec<String>> {
-        match ArgOrFieldCode::try_from(arg) {
-            Ok(arg) => match arg {
-                ArgOrFieldCode::SingleFileName | ArgOrFieldCode::SingleUrl => {
-                    uris.first().map(|uri| vec![uri.to_string()])
-                }
-                ArgOrFieldCode::FileList | ArgOrFieldCode::UrlList => {
-                    let args = uris.iter().map(|uri| uri.to_string()).collect();
-                    Some(args)
+/// A syntax error in an Exec value.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ExecSyntaxError {
+    #[error("unmatched quote")]
+    UnmatchedQuote,
+}
+
+/// Values substituted for the field codes that don't refer to the URLs.
+#[derive(Clone, Debug, Default)]
+pub struct FieldValues<'a> {
+    /// The value of the `Icon` key, for `%i`.
+    pub icon: Option<&'a str>,
+    /// The translated name of the application, for `%c`.
+    pub name: Option<&'a str>,
+    /// The location of the desktop file, for `%k`.
+    pub location: Option<&'a str>,
+}
+
+/// Splits an Exec value into arguments and expands its field codes, following the
+/// [Desktop Entry Specification](https://specifications.freedesktop.org/desktop-entry-spec/latest/exec-variables.html).
+///
+/// The value is expected to have its string escapes (e.g. `\s`) already decoded, as done when
+/// reading the desktop entry. Arguments can be quoted with double quotes, where `"`, `` ` ``, `$`
+/// and `\` must be escaped with a backslash. Single quotes and backslashes outside quotes are
+/// also accepted, as many desktop files rely on them.
+///
+/// Field codes are expanded anywhere in an argument (e.g. `--url=%u`), `%%` produces a literal
+/// `%`, and deprecated or unknown field codes are removed. `%F` and `%U` expand to one argument
+/// per URL when they are an argument on their own.
+///
+/// The file field codes `%f` and `%F` receive the local paths of the `file://` URLs, and skip the
+/// URLs that are not local.
+pub fn expand(
+    exec: &str,
+    uris: &[&str],
+    fields: &FieldValues,
+) -> std::result::Result<Vec<String>, ExecSyntaxError> {
+    Ok(expand_split(&split(exec)?, uris, fields))
+}
+
+/// Same as [expand], passing extra arguments to the application as done by [insert_args].
+pub fn expand_with_args(
+    exec: &str,
+    extra: &[String],
+    uris: &[&str],
+    fields: &FieldValues,
+) -> std::result::Result<Vec<String>, ExecSyntaxError> {
+    let mut args = split(exec)?;
+    insert_args(&mut args, extra);
+    Ok(expand_split(&args, uris, fields))
+}
+
+/// Expands the field codes of an Exec value already split into arguments.
+fn expand_split(args: &[String], uris: &[&str], fields: &FieldValues) -> Vec<String> {
+    // Only convert the URLs to paths when needed, as non-local ones are reported
+    let paths = match args
+        .iter()
+        .any(|arg| field_codes(arg).any(|code| matches!(code, 'f' | 'F')))
+    {
+        true => uris.iter().filter_map(|uri| local_path(uri)).collect(),
+        false => vec![],
+    };
+    let paths = paths.iter().map(String::as_str).collect::<Vec<_>>();
+
+    args.iter()
+        .flat_map(|arg| expand_arg(arg, uris, &paths, fields))
+        .collect()
+}
+
+/// Inserts arguments into an Exec value split into arguments, right before the first one with a
+/// URL or file field code, or at the end if there is none. Field codes in the inserted arguments
+/// are escaped, so they are passed as is.
+///
+/// Inserting them there, rather than right after the program, keeps them in the arguments of the
+/// application when it is started through another program, as in
+/// `env BAMF_DESKTOP_FILE_HINT=... /snap/bin/chromium %U`. The `@@u` and `@@` markers of
+/// `flatpak run --file-forwarding` are kept around the field code only.
+pub fn insert_args(args: &mut Vec<String>, extra: &[String]) {
+    let position = args
+        .iter()
+        .position(|arg| field_codes(arg).any(|code| matches!(code, 'f' | 'F' | 'u' | 'U')))
+        .map(
+            |index| match index.checked_sub(1).map(|before| args[before].as_str()) {
+                Some("@@" | "@@u") => index - 1,
+                _ => index,
+            },
+        )
+        .unwrap_or(args.len())
+        .max(1)
+        .min(args.len());
+
+    let extra = extra.iter().map(|arg| arg.replace('%', "%%"));
+    args.splice(position..position, extra);
+}
+
+/// What an Exec value can receive through its field codes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecTargets {
+    /// No URL or file field code, so nothing is passed to the application.
+    None,
+    /// Only `%f` or `%F`, which receive local file paths.
+    Files,
+    /// `%u` or `%U`, which receive URLs of any scheme.
+    Urls,
+}
+
+/// Finds what an Exec value can receive through its field codes.
+pub fn targets(exec: &str) -> ExecTargets {
+    let codes = split(exec)
+        .unwrap_or_default()
+        .iter()
+        .flat_map(|arg| field_codes(arg).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    if codes.iter().any(|code| matches!(code, 'u' | 'U')) {
+        ExecTargets::Urls
+    } else if codes.iter().any(|code| matches!(code, 'f' | 'F')) {
+        ExecTargets::Files
+    } else {
+        ExecTargets::None
+    }
+}
+
+/// Checks if an Exec value contains a field code that receives URLs or files.
+pub fn accepts_uris(exec: &str) -> bool {
+    targets(exec) != ExecTargets::None
+}
+
+/// Checks if an Exec value can receive several URLs or files at once, through a `%U` or `%F`
+/// argument.
+pub fn accepts_multiple(exec: &str) -> bool {
+    split(exec)
+        .unwrap_or_default()
+        .iter()
+        .any(|arg| arg == "%U" || arg == "%F")
+}
+
+/// Converts a `file://` URL to a percent-decoded local path. Values that are not URLs are
+/// considered paths already.
+fn local_path(uri: &str) -> Option<String> {
+    match Url::parse(uri) {
+        Ok(url) if url.scheme() == "file" => url
+            .to_file_path()
+            .map(|path| path.to_string_lossy().into_owned())
+            .inspect_err(|_| log::warn!("Skipping file URL without a local path: {uri}"))
+            .ok(),
+        Ok(_) => {
+            log::warn!("Skipping non-local URL for file field code: {uri}");
+            None
+        }
+        Err(_) => Some(uri.to_string()),
+    }
+}
+
+/// Splits an Exec value into arguments, removing quotes and escapes.
+pub(crate) fn split(exec: &str) -> std::result::Result<Vec<String>, ExecSyntaxError> {
+    let mut args = vec![];
+    let mut current: Option<String> = None;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => args.extend(current.take()),
+            '"' => {
+                let arg = current.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '`' | '$' | '\\')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err(ExecSyntaxError::UnmatchedQuote),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(ExecSyntaxError::UnmatchedQuote),
+                    }
                 }
-                ArgOrFieldCode::IconKey => self.de.icon().map(|icon| vec![icon.to_string()]),
-                ArgOrFieldCode::TranslatedName => self
-                    .de
-                    .name(self.locales)
-                    .map(|name| vec![name.to_string()]),
-                ArgOrFieldCode::DesktopFileLocation => {
-                    Some(vec![self.de.path.to_string_lossy().to_string()])
+            }
+            '\'' => {
+                let arg = current.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(ExecSyntaxError::UnmatchedQuote),
+                    }
                 }
-                ArgOrFieldCode::Arg(arg) => Some(vec![arg.to_string()]),
-            },
-            Err(e) => {
-                log::error!("{}", e);
-                None
             }
+            '\\' => {
+                let arg = current.get_or_insert_default();
+                arg.extend(chars.next());
+            }
+            c => current.get_or_insert_default().push(c),
         }
     }
+    args.extend(current);
+
+    Ok(args)
+}
+
+/// Iterates over the field codes in an argument, skipping `%%`.
+fn field_codes(arg: &str) -> impl Iterator<Item = char> + '_ {
+    let mut chars = arg.chars();
+    std::iter::from_fn(move || {
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                continue;
+            }
+            match chars.next() {
+                Some('%') => continue,
+                code => return code,
+            }
+        }
+        None
+    })
 }
 
-// either a command line argument or a field-code as described
-// in https://specifications.freedesktop.org/desktop-entry-spec/desktop-entry-spec-latest.html#exec-variables
-enum ArgOrFieldCode<'a> {
-    SingleFileName,
-    FileList,
-    SingleUrl,
-    UrlList,
-    IconKey,
-    TranslatedName,
-    DesktopFileLocation,
-    Arg(&'a str),
-}
-
-#[derive(Debug, thiserror::Error)]
-enum ExecErrorInternal<'a> {
-    #[error("Unknown field code: '{0}'")]
-    UnknownFieldCode(&'a str),
-
-    #[error("Deprecated field code: '{0}'")]
-    DeprecatedFieldCode(&'a str),
-}
-
-impl<'a> TryFrom<&'a str> for ArgOrFieldCode<'a> {
-    type Error = ExecErrorInternal<'a>;
-
-    // todo: handle escaping
-    fn try_from(value: &'a str) -> std::result::Result<Self, Self::Error> {
-        match value {
-            "%f" => Ok(ArgOrFieldCode::SingleFileName),
-            "%F" => Ok(ArgOrFieldCode::FileList),
-            "%u" => Ok(ArgOrFieldCode::SingleUrl),
-            "%U" => Ok(ArgOrFieldCode::UrlList),
-            "%i" => Ok(ArgOrFieldCode::IconKey),
-            "%c" => Ok(ArgOrFieldCode::TranslatedName),
-            "%k" => Ok(ArgOrFieldCode::DesktopFileLocation),
-            "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => {
-                Err(ExecErrorInternal::DeprecatedFieldCode(value))
+fn expand_arg(arg: &str, uris: &[&str], paths: &[&str], fields: &FieldValues) -> Vec<String> {
+    let to_vec = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+
+    match arg {
+        "%f" => to_vec(&paths[..paths.len().min(1)]),
+        "%F" => to_vec(paths),
+        "%u" => to_vec(&uris[..uris.len().min(1)]),
+        "%U" => to_vec(uris),
+        "%i" => fields
+            .icon
+            .map(|icon| to_vec(&["--icon", icon]))
+            .unwrap_or_default(),
+        "%c" => to_vec(fields.name.as_slice()),
+        "%k" => to_vec(fields.location.as_slice()),
+        _ => {
+            let mut expanded = String::with_capacity(arg.len());
+            let mut chars = arg.chars();
+
+            while let Some(c) = chars.next() {
+                if c != '%' {
+                    expanded.push(c);
+                    continue;
+                }
+
+                match chars.next() {
+                    Some('%') => expanded.push('%'),
+                    Some(code @ ('f' | 'u' | 'F' | 'U')) => {
+                        if code.is_ascii_uppercase() {
+                            log::warn!("Field code '%{code}' should be an argument on its own");
+                        }
+                        let targets = match code {
+                            'f' | 'F' => paths,
+                            _ => uris,
+                        };
+                        expanded.push_str(targets.first().unwrap_or(&""));
+                    }
+                    Some('i') => expanded.push_str(fields.icon.unwrap_or_default()),
+                    Some('c') => expanded.push_str(fields.name.unwrap_or_default()),
+                    Some('k') => expanded.push_str(fields.location.unwrap_or_default()),
+                    Some(code @ ('d' | 'D' | 'n' | 'N' | 'v' | 'm')) => {
+                        log::debug!("Ignoring deprecated field code: '%{code}'")
+                    }
+                    Some(code) => log::error!("Unknown field code: '%{code}'"),
+                    None => expanded.push('%'),
+                }
+            }
+
+            // Drop the arguments made only of field codes without a value
+            match expanded.is_empty() && !arg.is_empty() {
+                true => vec![],
+                false => vec![expanded],
             }
-            other if other.starts_with('%') => Err(ExecErrorInternal::UnknownFieldCode(other)),
-            other => Ok(ArgOrFieldCode::Arg(other)),
         }
     }
 }
+
+/// Replaced by the program and arguments of the wrapped command in command templates.
+pub(crate) const COMMAND_PLACEHOLDER: &str = "{command}";
+
+/// Runs a command through another one, given as a template split into arguments such as
+/// `kitty --single-instance {command}`.
+///
+/// The `{command}` placeholder is replaced by the program and arguments of the command. Without a
+/// placeholder, they are appended to the template. The other arguments of the template go through
+/// `expand`, to replace placeholders of their own.
+pub fn wrap_command(
+    template: &[String],
+    cmd: String,
+    args: Vec<String>,
+    expand: impl Fn(&str) -> String,
+) -> (String, Vec<String>) {
+    let command = std::iter::once(cmd).chain(args).collect::<Vec<_>>();
+
+    let mut wrapped = match template.iter().any(|arg| arg == COMMAND_PLACEHOLDER) {
+        true => template
+            .iter()
+            .flat_map(|arg| match arg == COMMAND_PLACEHOLDER {
+                true => command.clone(),
+                false => vec![expand(arg)],
+            })
+            .collect::<Vec<_>>(),
+        false => template
+            .iter()
+            .map(|arg| expand(arg))
+            .chain(command)
+            .collect(),
+    };
+
+    let program = wrapped.remove(0);
+    (program, wrapped)
+}
+
+/// Finds a program in the directories of `$PATH`, or checks it directly if it is a path.
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH").unwrap_or_default();
+    find_program_in(program, &paths)
+}
+
+/// Finds a program in the directories of a `PATH`-like list, or checks it directly if it is a
+/// path.
+pub fn find_program_in(program: &str, paths: &OsStr) -> Option<PathBuf> {
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return is_executable(&path).then_some(path);
+    }
+
+    std::env::split_paths(paths)
+        .map(|dir| dir.join(program))
+        .find(|path| is_executable(path))
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
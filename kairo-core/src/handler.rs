@@ -1,10 +1,73 @@
-use std::{path::PathBuf, process::Command};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use freedesktop_desktop_entry as fde;
 use mime::Mime;
+use serde::{Serialize, ser::SerializeStruct};
 use url::Url;
 
-use crate::{Error, Result, exec::ExecParser};
+use crate::{
+    ActivationToken,
+    DBusActivator,
+    Error,
+    LaunchWrapper,
+    ProgramRole,
+    Result,
+    exec::{self, ExecParseError, ExecParser, ExecTargets, FieldValues},
+    index::HandlerIndex,
+    mimeapps::MimeApps,
+    terminal::Terminal,
+    visibility::{ExcludedEntry, Exclusion, Visibility},
+};
+
+/// Something that can open URLs, such as an application or a command.
+///
+/// Implement it to add handlers that are not backed by a desktop entry, and wrap them with
+/// [UrlHandlerApp::from_handler] so they can be listed, routed and opened like any other app.
+pub trait UrlHandler: fmt::Debug + Send + Sync {
+    /// The unique ID of the handler, used in routing rules and remembered choices.
+    fn id(&self) -> &str;
+
+    /// The name shown to the user.
+    fn name(&self) -> &str;
+
+    fn comment(&self) -> Option<&str> {
+        None
+    }
+
+    fn icon(&self) -> fde::IconSource {
+        fde::IconSource::default()
+    }
+
+    /// The additional ways of opening URLs with the handler, such as "New Private Window".
+    fn actions(&self) -> Vec<AppAction> {
+        vec![]
+    }
+
+    /// Opens the URLs, using one of the handler's actions if given.
+    fn open(&self, urls: &[Url], action: Option<&str>, options: &LaunchOptions) -> Result<()>;
+}
+
+/// How a [UrlHandlerApp] is launched.
+#[derive(Clone, Debug, Default)]
+pub enum Launcher {
+    /// Runs the Exec key of the desktop entry at [UrlHandlerApp::path], or activates it through
+    /// D-Bus.
+    #[default]
+    DesktopEntry,
+    /// Runs a command given as the value of an Exec key, for the handlers defined in the
+    /// configuration.
+    Command(String),
+    /// Delegates to a handler from another source.
+    Handler(Arc<dyn UrlHandler>),
+}
 
 /// Represents an application that can handle specific URL schemes.
 #[derive(Clone, Debug)]
@@ -14,31 +77,559 @@ pub struct UrlHandlerApp {
     pub comment: Option<String>,
     pub icon: fde::IconSource,
     pub path: PathBuf,
+    pub actions: Vec<AppAction>,
+    /// Whether the application runs in a terminal emulator.
+    pub terminal: bool,
+    /// Whether this is the user's default application for the scheme, as set in `mimeapps.list`.
+    pub is_default: bool,
+    /// Extra arguments passed before the URLs in the Exec key (e.g. a browser profile).
+    pub args: Vec<String>,
+    /// Environment variables set for the launched command, on top of the inherited ones.
+    pub env: BTreeMap<String, String>,
+    /// The directory the command runs in, from the `Path` key of the desktop entry by default.
+    pub working_dir: Option<PathBuf>,
+    /// The wrapper of the command, instead of the one in the launch options.
+    pub wrapper: Option<LaunchWrapper>,
+    /// How the application is launched.
+    pub launcher: Launcher,
+}
+
+/// How long to watch a detached handler for early failures by default.
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_millis(300);
+
+const DETACHED_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Options that control how handlers are launched.
+#[derive(Clone, Debug)]
+pub struct LaunchOptions {
+    /// The terminal for applications with `Terminal=true`. If `None`, one is detected when needed.
+    pub terminal: Option<Terminal>,
+    /// Whether to return once the handler has started, instead of waiting for it to exit.
+    pub detach: bool,
+    /// How long to watch a detached handler for an early non-zero exit.
+    pub grace_period: Duration,
+    /// The token passed to the handler so it can take the focus, unless its desktop entry sets
+    /// `StartupNotify=false`. If `None`, the handler doesn't inherit the token Kairo was launched
+    /// with either.
+    pub activation_token: Option<ActivationToken>,
+    /// The command wrapping the handlers, unless they set one of their own.
+    pub wrapper: Option<LaunchWrapper>,
+    /// Environment variables set for the handlers, under the ones they set of their own.
+    pub env: BTreeMap<String, String>,
+    /// The directory the handlers run in, unless they set one of their own.
+    pub working_dir: Option<PathBuf>,
+    /// The bus applications with `DBusActivatable=true` are activated on. If `None`, the session
+    /// bus is connected to when needed.
+    pub dbus: Option<DBusActivator>,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self {
+            terminal: None,
+            detach: true,
+            grace_period: DEFAULT_GRACE_PERIOD,
+            activation_token: None,
+            wrapper: None,
+            env: BTreeMap::new(),
+            working_dir: None,
+            dbus: None,
+        }
+    }
+}
+
+/// The result of looking for the handlers of a scheme.
+#[derive(Clone, Debug)]
+pub struct HandlerScan {
+    /// The applications that can handle the scheme, the default one first.
+    pub apps: Vec<UrlHandlerApp>,
+    /// The entries associated with the scheme that were left out.
+    pub excluded: Vec<ExcludedEntry>,
+}
+
+/// A command built from an Exec key, along with the programs it needs: the application's, and the
+/// terminal emulator and launch wrapper it runs in.
+struct ExecCommand {
+    cmd: String,
+    args: Vec<String>,
+    programs: Vec<(ProgramRole, String)>,
+}
+
+/// URLs to open with the same application and action.
+#[derive(Clone, Debug)]
+pub struct UrlBatch {
+    pub app: UrlHandlerApp,
+    pub action: Option<AppAction>,
+    pub urls: Vec<Url>,
+}
+
+impl UrlBatch {
+    /// Opens all the URLs, launching the application once if it accepts several of them.
+    pub fn open(&self, options: &LaunchOptions) -> Result<()> {
+        let action = self.action.as_ref().map(|action| action.id.as_str());
+        self.app.open_urls_with_options(&self.urls, action, options)
+    }
+}
+
+/// Groups URLs by the application and action chosen to open them, in the order they were first
+/// chosen.
+#[derive(Clone, Debug, Default)]
+pub struct UrlBatches {
+    batches: Vec<UrlBatch>,
+}
+
+impl UrlBatches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the URL to the batch of the application and action, creating it if needed. Apps
+    /// launched with a different environment, working directory or wrapper get batches of their
+    /// own.
+    pub fn push(&mut self, app: &UrlHandlerApp, action: Option<&AppAction>, url: Url) {
+        let batch = self.batches.iter_mut().find(|batch| {
+            batch.app.appid == app.appid
+                && batch.action.as_ref() == action
+                && batch.app.env == app.env
+                && batch.app.working_dir == app.working_dir
+                && batch.app.wrapper == app.wrapper
+        });
+
+        match batch {
+            Some(batch) => batch.urls.push(url),
+            None => self.batches.push(UrlBatch {
+                app: app.clone(),
+                action: action.cloned(),
+                urls: vec![url],
+            }),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &UrlBatch> {
+        self.batches.iter()
+    }
+}
+
+/// An additional way of launching an application, declared in a `[Desktop Action]` group
+/// (e.g. "New Private Window").
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AppAction {
+    pub id: String,
+    pub name: String,
 }
 
 impl UrlHandlerApp {
     /// Opens the given URL with this application.
     pub fn open_url(&self, url: Url) -> Result<()> {
-        log::info!("Opening URL with {}: {url}", self.appid);
+        self.open_url_with_action(url, None)
+    }
+
+    /// Opens the given URL using one of the application's actions.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to open.
+    /// * `action` - The ID of the action, as listed in [UrlHandlerApp::actions]. If `None`, the
+    ///   application is launched with its main command.
+    pub fn open_url_with_action(&self, url: Url, action: Option<&str>) -> Result<()> {
+        self.open_url_with_options(url, action, &LaunchOptions::default())
+    }
+
+    /// Opens the given URL using one of the application's actions and the given launch options.
+    ///
+    /// See [UrlHandlerApp::open_urls_with_options] for how the application is launched.
+    pub fn open_url_with_options(
+        &self,
+        url: Url,
+        action: Option<&str>,
+        options: &LaunchOptions,
+    ) -> Result<()> {
+        self.open_urls_with_options(std::slice::from_ref(&url), action, options)
+    }
+
+    /// Opens several URLs using one of the application's actions and the given launch options.
+    ///
+    /// Applications with `DBusActivatable=true` are activated through D-Bus when opened with their
+    /// main command, falling back to their Exec key if that fails. Commands with a `%U` or `%F`
+    /// argument are launched once with all the URLs, while the others are launched once per URL.
+    /// Commands run with the app's [UrlHandlerApp::env] and in its [UrlHandlerApp::working_dir].
+    ///
+    /// With the default options, the application is detached and this returns once it has
+    /// started, failing only if it couldn't be spawned or exits with an error right away.
+    pub fn open_urls_with_options(
+        &self,
+        urls: &[Url],
+        action: Option<&str>,
+        options: &LaunchOptions,
+    ) -> Result<()> {
+        let uris = urls.iter().map(Url::as_str).collect::<Vec<_>>();
+        match action {
+            Some(action) => log::info!("Opening URLs with {} ({action}): {uris:?}", self.appid),
+            None => log::info!("Opening URLs with {}: {uris:?}", self.appid),
+        }
+
+        // Handlers from other sources launch their own commands, so they get the app's settings
+        if let Launcher::Handler(handler) = &self.launcher {
+            return handler.open(urls, action, &self.launch_options(options));
+        }
+
+        let locales = fde::get_languages_from_env();
+        let de = self.desktop_entry(&locales)?;
+
+        if self.dbus_activation(de.as_ref(), action, options) {
+            let dbus = match &options.dbus {
+                Some(dbus) => Ok(dbus.clone()),
+                None => DBusActivator::session(),
+            };
+            match dbus
+                .and_then(|dbus| dbus.open(&self.appid, &uris, options.activation_token.as_ref()))
+            {
+                Ok(()) => return Ok(()),
+                Err(e) => log::warn!(
+                    "Failed to activate {} through D-Bus, falling back to Exec: {e}",
+                    self.appid
+                ),
+            }
+        }
+
+        for command in self.exec_commands(de.as_ref(), &locales, &uris, action, options)? {
+            for (role, program) in &command.programs {
+                self.check_program(*role, program)?;
+            }
+            self.launch(command.cmd, command.args, de.as_ref(), options)?;
+        }
+
+        Ok(())
+    }
+
+    /// The commands that would open the URLs, wrapped in a terminal emulator and the launch
+    /// wrapper when needed, without running them.
+    ///
+    /// Applications activated through D-Bus (see [UrlHandlerApp::uses_dbus]) only run these
+    /// commands if that fails, and handlers wrapping a [UrlHandler] have none.
+    pub fn commands(
+        &self,
+        urls: &[Url],
+        action: Option<&str>,
+        options: &LaunchOptions,
+    ) -> Result<Vec<Vec<String>>> {
+        if let Launcher::Handler(_) = &self.launcher {
+            return Ok(vec![]);
+        }
+
+        let uris = urls.iter().map(Url::as_str).collect::<Vec<_>>();
+        let locales = fde::get_languages_from_env();
+        let de = self.desktop_entry(&locales)?;
+
+        Ok(self
+            .exec_commands(de.as_ref(), &locales, &uris, action, options)?
+            .into_iter()
+            .map(|command| [vec![command.cmd], command.args].concat())
+            .collect())
+    }
+
+    /// Whether the application is activated through D-Bus when opened with the given action and
+    /// options.
+    pub fn uses_dbus(&self, action: Option<&str>, options: &LaunchOptions) -> Result<bool> {
+        if let Launcher::Handler(_) = &self.launcher {
+            return Ok(false);
+        }
 
         let locales = fde::get_languages_from_env();
-        let de = fde::DesktopEntry::from_path(self.path.clone(), Some(&locales))?;
+        let de = self.desktop_entry(&locales)?;
+        Ok(self.dbus_activation(de.as_ref(), action, options))
+    }
+
+    /// The options with the environment, working directory and wrapper of the app applied.
+    fn launch_options(&self, options: &LaunchOptions) -> LaunchOptions {
+        LaunchOptions {
+            env: options
+                .env
+                .iter()
+                .chain(&self.env)
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            working_dir: self.working_dir.clone().or(options.working_dir.clone()),
+            wrapper: self.wrapper.clone().or(options.wrapper.clone()),
+            ..options.clone()
+        }
+    }
+
+    fn dbus_activation(
+        &self,
+        de: Option<&fde::DesktopEntry>,
+        action: Option<&str>,
+        options: &LaunchOptions,
+    ) -> bool {
+        // Actions, extra arguments, environment variables, working directories and launch
+        // wrappers only apply to the Exec key, which is run instead when any of them is set
+        de.is_some_and(|de| de.dbus_activatable())
+            && action.is_none()
+            && self.args.is_empty()
+            && self.env.is_empty()
+            && options.env.is_empty()
+            && self.working_dir.is_none()
+            && options.working_dir.is_none()
+            && self.wrapper.is_none()
+            && options.wrapper.is_none()
+    }
+
+    /// Builds the commands from the Exec key, or the command of handlers without a desktop
+    /// entry: once with all the URLs if it accepts several of them, otherwise once per URL.
+    fn exec_commands(
+        &self,
+        de: Option<&fde::DesktopEntry>,
+        locales: &[String],
+        uris: &[&str],
+        action: Option<&str>,
+        options: &LaunchOptions,
+    ) -> Result<Vec<ExecCommand>> {
+        let exec = match (de, &self.launcher) {
+            (Some(de), _) => match action {
+                Some(action) => de.action_exec(action),
+                None => de.exec(),
+            },
+            (None, Launcher::Command(command)) => Some(command.as_str()),
+            (None, _) => None,
+        };
+        // Commands without URL field codes are launched once, as they would ignore the URLs
+        let batches = match exec
+            .is_none_or(|exec| exec::accepts_multiple(exec) || !exec::accepts_uris(exec))
+        {
+            true => vec![uris],
+            false => uris.chunks(1).collect(),
+        };
+
+        let Some(de) = de else {
+            let command = exec.unwrap_or_default();
+            return batches
+                .into_iter()
+                .map(|batch| {
+                    let (cmd, args) = self.parse_command(command, batch)?;
+                    self.wrap_command(cmd, args, self.terminal, options)
+                })
+                .collect();
+        };
+
+        let parser = ExecParser::new(de, locales).with_args(&self.args);
+        batches
+            .into_iter()
+            .map(|batch| {
+                let (cmd, args) = match action {
+                    Some(action) => parser.parse_action_with_uris(action, batch)?,
+                    None => parser.parse_with_uris(batch)?,
+                };
+
+                self.wrap_command(cmd, args, de.terminal(), options)
+            })
+            .collect()
+    }
+
+    /// Expands the command of a handler defined in the configuration, given as the value of an
+    /// Exec key.
+    fn parse_command(&self, command: &str, uris: &[&str]) -> Result<(String, Vec<String>)> {
+        let fields = FieldValues {
+            name: Some(&self.name),
+            ..FieldValues::default()
+        };
+        let args = exec::expand_with_args(command, &self.args, uris, &fields).map_err(|e| {
+            ExecParseError::InvalidFormat {
+                reason: e.to_string(),
+                path: self.path.clone().into(),
+            }
+        })?;
+
+        match args.as_slice() {
+            [cmd, args @ ..] => Ok((cmd.to_string(), args.to_vec())),
+            _ => Err(ExecParseError::InvalidExecArgs {
+                path: self.path.clone().into(),
+            })?,
+        }
+    }
+
+    /// Wraps the command in a terminal emulator if needed, and then in the launch wrapper.
+    fn wrap_command(
+        &self,
+        cmd: String,
+        args: Vec<String>,
+        terminal: bool,
+        options: &LaunchOptions,
+    ) -> Result<ExecCommand> {
+        let mut programs = vec![(ProgramRole::App, cmd.clone())];
+
+        let (cmd, args) = match terminal {
+            true => {
+                let terminal = options
+                    .terminal
+                    .clone()
+                    .or_else(Terminal::detect)
+                    .ok_or_else(|| Error::NoTerminalFound(self.appid.clone()))?;
+                let (cmd, args) = terminal.command(cmd, args);
+                programs.push((ProgramRole::Terminal, cmd.clone()));
+                (cmd, args)
+            }
+            false => (cmd, args),
+        };
+        let (cmd, args) = match self.wrapper.as_ref().or(options.wrapper.as_ref()) {
+            Some(wrapper) => {
+                let (cmd, args) = wrapper.command(&self.appid, &self.path, cmd, args);
+                programs.push((ProgramRole::Wrapper, cmd.clone()));
+                (cmd, args)
+            }
+            None => (cmd, args),
+        };
+
+        Ok(ExecCommand {
+            cmd,
+            args,
+            programs,
+        })
+    }
 
-        let (cmd, args) = ExecParser::new(&de, &locales).parse_with_uris(&[url.as_str()])?;
+    /// Reads the application's desktop entry, if it is launched from one.
+    fn desktop_entry(&self, locales: &[String]) -> Result<Option<fde::DesktopEntry>> {
+        match &self.launcher {
+            Launcher::DesktopEntry => Ok(Some(fde::DesktopEntry::from_path(
+                self.path.clone(),
+                Some(locales),
+            )?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Runs the command with the app's environment and working directory, either detached or
+    /// waiting for it.
+    fn launch(
+        &self,
+        cmd: String,
+        args: Vec<String>,
+        de: Option<&fde::DesktopEntry>,
+        options: &LaunchOptions,
+    ) -> Result<()> {
         log::debug!("Executing command: '{cmd}' with args: {args:?}");
 
-        let mut program = Command::new(cmd).args(args).spawn()?;
-        let status = program.wait()?;
+        let mut command = Command::new(cmd);
+        command.args(args).envs(&options.env).envs(&self.env);
+        match self.working_dir.as_ref().or(options.working_dir.as_ref()) {
+            Some(dir) if dir.is_dir() => {
+                command.current_dir(dir);
+            }
+            Some(dir) => log::warn!(
+                "Working directory {dir:?} of {} does not exist, ignoring it",
+                self.appid
+            ),
+            None => {}
+        }
+        // Entries without the StartupNotify key may still support it, so only opt out explicitly
+        match &options.activation_token {
+            Some(token)
+                if de.is_none_or(|de| de.desktop_entry("StartupNotify") != Some("false")) =>
+            {
+                log::debug!("Passing activation token {token:?} to {}", self.appid);
+                token.set_env(&mut command);
+            }
+            _ => ActivationToken::remove_env(&mut command),
+        }
+
+        match options.detach {
+            true => self.spawn_detached(command, options.grace_period),
+            false => {
+                let status = command.spawn()?.wait()?;
+                match status.success() {
+                    true => Ok(()),
+                    false => Err(Error::OpenUrl(self.appid.clone(), status)),
+                }
+            }
+        }
+    }
+
+    /// Checks that the program is installed, so a missing one is reported with the app it
+    /// belongs to instead of a bare I/O error.
+    fn check_program(&self, role: ProgramRole, program: &str) -> Result<()> {
+        match exec::find_program(program) {
+            Some(path) => {
+                log::trace!("Found {program} at {path:?}");
+                Ok(())
+            }
+            None => Err(Error::ProgramNotFound {
+                appid: self.appid.clone(),
+                path: self.path.clone().into(),
+                program: program.to_string(),
+                role,
+            }),
+        }
+    }
+
+    /// Starts the command in a new session without a controlling terminal, returning as soon as
+    /// it has been running for the grace period or has exited successfully.
+    ///
+    /// A background thread keeps waiting for the process so it gets reaped if it exits while we
+    /// are still running. Otherwise, it is reparented and reaped by the init process.
+    fn spawn_detached(&self, mut command: Command, grace_period: Duration) -> Result<()> {
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        // SAFETY: setsid is async-signal-safe, so it can be called between fork and exec
+        unsafe {
+            command.pre_exec(|| match libc::setsid() {
+                -1 => Err(std::io::Error::last_os_error()),
+                _ => Ok(()),
+            });
+        }
+
+        let mut child = command.spawn()?;
+        let started = Instant::now();
 
-        if !status.success() {
-            return Err(Error::OpenUrl(self.appid.clone(), status));
+        while started.elapsed() < grace_period {
+            if let Some(status) = child.try_wait()? {
+                log::debug!(
+                    "{} exited during the grace period with {status}",
+                    self.appid
+                );
+                return match status.success() {
+                    true => Ok(()),
+                    false => Err(Error::OpenUrl(self.appid.clone(), status)),
+                };
+            }
+            std::thread::sleep(DETACHED_POLL_INTERVAL);
         }
 
+        log::debug!("{} is running with PID {}", self.appid, child.id());
+        std::thread::spawn(move || child.wait());
+
         Ok(())
     }
 
+    /// Finds one of the application's actions by its ID.
+    pub fn action(&self, id: &str) -> Option<&AppAction> {
+        self.actions.iter().find(|action| action.id == id)
+    }
+
+    /// The application's main command followed by each of its actions.
+    pub fn launch_targets(&self) -> impl Iterator<Item = Option<&AppAction>> {
+        std::iter::once(None).chain(self.actions.iter().map(Some))
+    }
+
+    /// The name of the application followed by the name of the action, if any.
+    pub fn display_name(&self, action: Option<&AppAction>) -> String {
+        match action {
+            Some(action) => format!("{} — {}", self.name, action.name),
+            None => self.name.clone(),
+        }
+    }
+
     /// Retrieves all applications that can handle the specified URL scheme.
     ///
+    /// The associations from the user's and system's `mimeapps.list` files are honoured: the
+    /// default application comes first and is flagged with [UrlHandlerApp::is_default], followed
+    /// by the added associations, while removed associations are left out. Applications and
+    /// actions that only accept local files (`%f`/`%F`) are left out for schemes other than `file`.
+    ///
     /// # Arguments
     ///
     /// * `scheme` - The URL scheme to query (e.g., "http", "mailto").
@@ -49,6 +640,74 @@ impl UrlHandlerApp {
         locales: Option<Vec<String>>,
         search_paths: Option<Vec<PathBuf>>,
     ) -> Result<Vec<Self>> {
+        Self::handlers_for_scheme_with(scheme, locales, search_paths, &MimeApps::load())
+    }
+
+    /// Same as [UrlHandlerApp::handlers_for_scheme], using the given MIME associations.
+    pub fn handlers_for_scheme_with(
+        scheme: &str,
+        locales: Option<Vec<String>>,
+        search_paths: Option<Vec<PathBuf>>,
+        mime_apps: &MimeApps,
+    ) -> Result<Vec<Self>> {
+        let scan = Self::scan_scheme(
+            scheme,
+            locales,
+            search_paths,
+            mime_apps,
+            &Visibility::from_env(),
+        )?;
+
+        if scan.apps.is_empty() {
+            return Err(Error::NoHandlersFound(scheme.to_string()));
+        }
+
+        Ok(scan.apps)
+    }
+
+    /// Finds the applications that can handle the scheme, along with the entries associated with
+    /// it that were left out and why.
+    ///
+    /// The desktop files are looked up in the handler index cached in `$XDG_CACHE_HOME/kairo`,
+    /// which is updated with the files that changed since the last run. See
+    /// [UrlHandlerApp::scan_scheme_with_index] for the rules applied.
+    pub fn scan_scheme(
+        scheme: &str,
+        locales: Option<Vec<String>>,
+        search_paths: Option<Vec<PathBuf>>,
+        mime_apps: &MimeApps,
+        visibility: &Visibility,
+    ) -> Result<HandlerScan> {
+        let mut index = HandlerIndex::open_default().unwrap_or_else(|e| {
+            log::warn!("Failed to open the handler index: {e}");
+            HandlerIndex::in_memory()
+        });
+
+        Self::scan_scheme_with_index(
+            scheme,
+            locales,
+            search_paths,
+            mime_apps,
+            visibility,
+            &mut index,
+        )
+    }
+
+    /// Same as [UrlHandlerApp::scan_scheme], using the given handler index.
+    ///
+    /// When several entries share the same desktop file ID, only the one in the most important
+    /// search path is considered. Besides the MIME associations, entries are filtered by the
+    /// `Hidden`, `NoDisplay`, `TryExec`, `OnlyShowIn` and `NotShowIn` keys. Entries with
+    /// `NoDisplay=true` are kept when they are explicitly associated in `mimeapps.list`, as this
+    /// is how ad-hoc handlers are usually registered.
+    pub fn scan_scheme_with_index(
+        scheme: &str,
+        locales: Option<Vec<String>>,
+        search_paths: Option<Vec<PathBuf>>,
+        mime_apps: &MimeApps,
+        visibility: &Visibility,
+        index: &mut HandlerIndex,
+    ) -> Result<HandlerScan> {
         let locales = locales.unwrap_or_else(fde::get_languages_from_env);
         let search_paths = search_paths.unwrap_or_else(|| fde::default_paths().collect());
 
@@ -56,31 +715,117 @@ impl UrlHandlerApp {
             "Searching for applications handling scheme '{scheme}' in paths: {search_paths:?}"
         );
 
-        let entries = fde::Iter::new(search_paths.into_iter()).entries(Some(&locales));
+        if index.update(&search_paths)
+            && let Err(e) = index.save()
+        {
+            log::warn!("Failed to save the handler index: {e}");
+        }
+
         let scheme_handler_mime = format!("x-scheme-handler/{scheme}")
             .as_str()
             .parse::<Mime>()?;
+        let associations = mime_apps.associations(scheme_handler_mime.essence_str());
+        // Only parse the entries that may be related to the scheme
+        let entries = index
+            .entries(&search_paths)
+            .into_iter()
+            .filter(|entry| {
+                entry.declares(scheme_handler_mime.essence_str())
+                    || associations.is_explicit(&entry.id)
+            })
+            .filter_map(
+                |entry| match fde::DesktopEntry::from_path(&entry.path, Some(&locales)) {
+                    Ok(mut de) => {
+                        de.appid = entry.id.clone();
+                        Some(de)
+                    }
+                    Err(e) => {
+                        log::debug!("Failed to parse {:?}: {e}", entry.path);
+                        None
+                    }
+                },
+            )
+            .collect::<Vec<_>>();
+        // Handlers that only take file paths can't open URLs of other schemes
+        let local = scheme.eq_ignore_ascii_case("file");
+        let handles_scheme = |exec: Option<&str>| {
+            local || exec.is_none_or(|exec| exec::targets(exec) != ExecTargets::Files)
+        };
 
-        let apps = entries
+        let mut excluded = vec![];
+        let entries = entries
+            .into_iter()
+            // Ignore self on the list
+            .filter(|de| !de.id().eq_ignore_ascii_case("kairo"))
             .filter(|de| {
-                de.mime_type()
-                    .is_some_and(|mime| mime.contains(&scheme_handler_mime.essence_str()))
-                    // Ignore self on the list
-                    && !de.id().eq_ignore_ascii_case("kairo")
+                let declares_mime = de
+                    .mime_type()
+                    .is_some_and(|mime| mime.contains(&scheme_handler_mime.essence_str()));
+
+                let reason = match visibility.check(de) {
+                    _ if !associations.is_associated(&de.appid, declares_mime) => {
+                        match declares_mime {
+                            true => Some(Exclusion::RemovedAssociation),
+                            // Not related to the scheme at all
+                            false => return false,
+                        }
+                    }
+                    _ if !handles_scheme(de.exec()) => Some(Exclusion::FilesOnly),
+                    Err(Exclusion::NoDisplay) if associations.is_explicit(&de.appid) => None,
+                    Err(reason) => Some(reason),
+                    Ok(()) => None,
+                };
+
+                match reason {
+                    Some(reason) => {
+                        log::info!("Excluding {}: {reason}", de.appid);
+                        excluded.push(ExcludedEntry {
+                            appid: de.appid.clone(),
+                            path: de.path.clone(),
+                            reason,
+                        });
+                        false
+                    }
+                    None => true,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let installed = entries
+            .iter()
+            .map(|de| de.appid.as_str())
+            .collect::<Vec<_>>();
+        let default = associations.default_app(&installed).map(str::to_string);
+        if let Some(default) = &default {
+            log::debug!("Default application for '{scheme_handler_mime}' is {default}");
+        }
+
+        let mut apps = entries
+            .into_iter()
+            .map(|entry| {
+                let file_only_actions = entry
+                    .actions()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|id| !handles_scheme(entry.action_exec(id)))
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+
+                let mut app = Self::from_desktop_entry(entry, &locales);
+                app.is_default = default.as_ref() == Some(&app.appid);
+                app.actions
+                    .retain(|action| !file_only_actions.contains(&action.id));
+                app
             })
-            .map(|entry| Self::from_desktop_entry(entry, &locales))
             .collect::<Vec<_>>();
+        apps.sort_by_key(|app| associations.rank(&app.appid, default.as_deref()));
 
         log::info!(
             "Found {} applications with support for '{scheme_handler_mime}'",
             apps.len(),
         );
 
-        if apps.is_empty() {
-            return Err(Error::NoHandlersFound(scheme.to_string()));
-        }
-
-        Ok(apps)
+        Ok(HandlerScan { apps, excluded })
     }
 
     /// Creates an [App] instance from a [freedesktop_desktop_entry::DesktopEntry].
@@ -96,6 +841,23 @@ impl UrlHandlerApp {
             .map(|name| name.into())
             .unwrap_or_else(|| appid.clone());
 
+        // Only keep the actions that can receive the URL
+        let actions = de
+            .actions()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|id| !id.is_empty())
+            .filter(|id| de.action_exec(id).is_some_and(exec::accepts_uris))
+            .map(|id| AppAction {
+                id: id.to_string(),
+                name: de
+                    .action_name(id, locales)
+                    .map(|name| name.into())
+                    .unwrap_or_else(|| id.to_string()),
+            })
+            .collect();
+        let working_dir = de.path().filter(|path| !path.is_empty()).map(PathBuf::from);
+
         Self {
             appid,
             name,
@@ -104,8 +866,48 @@ impl UrlHandlerApp {
                 .icon()
                 .map(fde::IconSource::from_unknown)
                 .unwrap_or_default(),
+            terminal: de.terminal(),
             path: de.path,
+            actions,
+            is_default: false,
+            args: vec![],
+            env: BTreeMap::new(),
+            working_dir,
+            wrapper: None,
+            launcher: Launcher::DesktopEntry,
+        }
+    }
+
+    /// Creates an app for a handler that is not backed by a desktop entry.
+    pub fn from_handler(handler: Arc<dyn UrlHandler>) -> Self {
+        Self {
+            appid: handler.id().to_string(),
+            name: handler.name().to_string(),
+            comment: handler.comment().map(str::to_string),
+            icon: handler.icon(),
+            path: PathBuf::new(),
+            actions: handler.actions(),
+            terminal: false,
+            is_default: false,
+            args: vec![],
+            env: BTreeMap::new(),
+            working_dir: None,
+            wrapper: None,
+            launcher: Launcher::Handler(handler),
+        }
+    }
+
+    /// Returns a copy of the app launched with the extra environment variables, and in the given
+    /// working directory if set. Variables already set for the app are overridden.
+    pub fn with_env(&self, env: &BTreeMap<String, String>, working_dir: Option<&Path>) -> Self {
+        let mut app = self.clone();
+        app.env
+            .extend(env.iter().map(|(key, value)| (key.clone(), value.clone())));
+        if let Some(dir) = working_dir {
+            app.working_dir = Some(dir.to_path_buf());
         }
+
+        app
     }
 
     pub fn icon_path(&self, icon_size: u16) -> Option<std::path::PathBuf> {
@@ -124,3 +926,56 @@ impl UrlHandlerApp {
         }
     }
 }
+
+/// Serializes the information shown about the application, with its icon as named in the desktop
+/// entry. How it is launched is left out, and nothing is looked up, see [UrlHandlerApp::icon_path]
+/// to resolve the icon.
+impl Serialize for UrlHandlerApp {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let icon = match &self.icon {
+            fde::IconSource::Name(name) if name.is_empty() => None,
+            fde::IconSource::Name(name) => Some(name.clone()),
+            fde::IconSource::Path(path) => Some(path.to_string_lossy().into_owned()),
+        };
+
+        let mut app = serializer.serialize_struct("UrlHandlerApp", 8)?;
+        app.serialize_field("appid", &self.appid)?;
+        app.serialize_field("name", &self.name)?;
+        app.serialize_field("comment", &self.comment)?;
+        app.serialize_field("icon", &icon)?;
+        app.serialize_field("path", &self.path)?;
+        app.serialize_field("is_default", &self.is_default)?;
+        app.serialize_field("terminal", &self.terminal)?;
+        app.serialize_field("actions", &self.actions)?;
+        app.end()
+    }
+}
+
+impl UrlHandler for UrlHandlerApp {
+    fn id(&self) -> &str {
+        &self.appid
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    fn icon(&self) -> fde::IconSource {
+        self.icon.clone()
+    }
+
+    fn actions(&self) -> Vec<AppAction> {
+        self.actions.clone()
+    }
+
+    fn open(&self, urls: &[Url], action: Option<&str>, options: &LaunchOptions) -> Result<()> {
+        self.open_urls_with_options(urls, action, options)
+    }
+}
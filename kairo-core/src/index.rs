@@ -0,0 +1,255 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
+};
+
+use freedesktop_desktop_entry as fde;
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, desktop_id};
+
+const INDEX_FILE: &str = "handlers.json";
+
+/// Distinguishes the temporary files written by each save in this process.
+static SAVE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A desktop file found in one of the search paths.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedEntry {
+    /// The desktop file ID, used as the app ID.
+    pub id: String,
+    pub path: PathBuf,
+    /// The MIME types declared in the `MimeType` key, lowercased.
+    pub mime_types: Vec<String>,
+    /// Whether the file could be parsed. Invalid files don't mask the ones with the same ID.
+    pub valid: bool,
+    modified: Option<SystemTime>,
+}
+
+impl IndexedEntry {
+    fn parse(id: String, path: PathBuf, modified: Option<SystemTime>) -> Self {
+        log::trace!("Indexing {path:?}");
+
+        let (mime_types, valid) = match fde::DesktopEntry::from_path(&path, None::<&[&str]>) {
+            Ok(de) => (
+                de.mime_type()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|mime| !mime.is_empty())
+                    .map(str::to_ascii_lowercase)
+                    .collect(),
+                true,
+            ),
+            Err(e) => {
+                log::debug!("Failed to parse {path:?}: {e}");
+                (vec![], false)
+            }
+        };
+
+        Self {
+            id,
+            path,
+            mime_types,
+            valid,
+            modified,
+        }
+    }
+
+    /// Checks if the entry declares the MIME type.
+    pub fn declares(&self, mime: &str) -> bool {
+        self.mime_types
+            .iter()
+            .any(|declared| declared.eq_ignore_ascii_case(mime))
+    }
+}
+
+/// The desktop files found in a search path, along with the modification time of each directory
+/// walked to find them.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct IndexedDir {
+    dirs: BTreeMap<PathBuf, Option<SystemTime>>,
+    entries: Vec<IndexedEntry>,
+}
+
+/// An on-disk index of the desktop files in each search path and the MIME types they declare, so
+/// the handlers of a scheme can be found without parsing every desktop file.
+///
+/// The index is kept up to date using the modification times of the directories and files:
+/// directories are walked again only when an entry was added or removed, and files are parsed
+/// again only when they changed.
+#[derive(Clone, Debug, Default)]
+pub struct HandlerIndex {
+    path: Option<PathBuf>,
+    dirs: BTreeMap<PathBuf, IndexedDir>,
+}
+
+impl HandlerIndex {
+    /// Opens the index at `$XDG_CACHE_HOME/kairo/handlers.json`.
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::open(Self::default_path()?))
+    }
+
+    /// The path of the index in `$XDG_CACHE_HOME/kairo`, creating the directory if needed.
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(xdg::BaseDirectories::with_prefix("kairo").place_cache_file(INDEX_FILE)?)
+    }
+
+    /// Opens the index at the given path. A missing or unreadable file is treated as an empty
+    /// index, as it can always be rebuilt.
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        log::debug!("Loading handler index from {path:?}");
+
+        let dirs = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log::warn!("Ignoring invalid handler index {path:?}: {e}");
+                BTreeMap::default()
+            }),
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("Failed to read handler index {path:?}: {e}");
+                }
+                BTreeMap::default()
+            }
+        };
+
+        Self {
+            path: Some(path),
+            dirs,
+        }
+    }
+
+    /// Creates an empty index that is only kept in memory.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Brings the index up to date with the given search paths, returning whether anything
+    /// changed. Search paths that no longer exist are dropped from the index.
+    pub fn update(&mut self, search_paths: &[PathBuf]) -> bool {
+        let mut changed = false;
+
+        let missing = self
+            .dirs
+            .keys()
+            .filter(|root| !root.is_dir())
+            .cloned()
+            .collect::<Vec<_>>();
+        for root in missing {
+            log::debug!("Removing {root:?} from the handler index");
+            self.dirs.remove(&root);
+            changed = true;
+        }
+
+        for root in search_paths.iter().filter(|root| root.is_dir()) {
+            let cached = self.dirs.remove(root);
+            let (dir, updated) = match cached {
+                Some(cached) if cached.dirs.iter().all(|(dir, time)| modified(dir) == *time) => {
+                    refresh_entries(cached)
+                }
+                cached => (scan(root, cached.unwrap_or_default()), true),
+            };
+
+            changed |= updated;
+            self.dirs.insert(root.clone(), dir);
+        }
+
+        changed
+    }
+
+    /// Writes the index back to disk, if it has a path.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        log::debug!("Saving handler index to {path:?}");
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first, so concurrent instances never read a partial index
+        let temp = path.with_extension(format!(
+            "json.{}.{}",
+            std::process::id(),
+            SAVE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&temp, serde_json::to_string(&self.dirs)?)?;
+        std::fs::rename(&temp, path)?;
+
+        Ok(())
+    }
+
+    /// The valid entries in the given search paths, keeping only the first one found for each
+    /// desktop file ID, most important first.
+    ///
+    /// The search paths must have been passed to [HandlerIndex::update] before.
+    pub fn entries(&self, search_paths: &[PathBuf]) -> Vec<&IndexedEntry> {
+        let mut ids = HashSet::new();
+
+        search_paths
+            .iter()
+            .filter_map(|root| self.dirs.get(root))
+            .flat_map(|dir| &dir.entries)
+            .filter(|entry| entry.valid && ids.insert(entry.id.as_str()))
+            .collect()
+    }
+}
+
+/// Parses again the entries whose files changed since they were indexed.
+fn refresh_entries(mut dir: IndexedDir) -> (IndexedDir, bool) {
+    let mut changed = false;
+
+    for entry in &mut dir.entries {
+        let time = modified(&entry.path);
+        if time != entry.modified {
+            *entry = IndexedEntry::parse(entry.id.clone(), entry.path.clone(), time);
+            changed = true;
+        }
+    }
+
+    (dir, changed)
+}
+
+/// Walks the search path again, reusing the entries of the files that didn't change.
+fn scan(root: &Path, cached: IndexedDir) -> IndexedDir {
+    log::debug!("Scanning {root:?} for desktop entries");
+
+    let mut cached = cached
+        .entries
+        .into_iter()
+        .map(|entry| (entry.path.clone(), entry))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut visited = BTreeSet::new();
+    let mut files = vec![];
+    desktop_id::walk(root, &mut visited, &mut files);
+
+    let entries = files
+        .into_iter()
+        .filter_map(|path| {
+            let id = desktop_id::desktop_id(root, &path)?;
+            let time = modified(&path);
+            Some(match cached.remove(&path) {
+                Some(entry) if entry.id == id && entry.modified == time => entry,
+                _ => IndexedEntry::parse(id, path, time),
+            })
+        })
+        .collect();
+    let dirs = visited
+        .into_iter()
+        .map(|dir| {
+            let time = modified(&dir);
+            (dir, time)
+        })
+        .collect();
+
+    IndexedDir { dirs, entries }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -1,9 +1,47 @@
+pub mod choices;
+pub mod clean;
+pub mod config;
+pub mod dbus;
+pub mod desktop_id;
 mod error;
 pub mod exec;
 mod handler;
+pub mod index;
+pub mod mimeapps;
+pub mod profiles;
+pub mod redirect;
+pub mod router;
+pub mod source;
+pub mod startup;
+pub mod terminal;
+pub mod visibility;
+pub mod wrapper;
 
 pub type Result<T> = std::result::Result<T, error::Error>;
 
-pub use error::Error;
-pub use handler::UrlHandlerApp;
+pub use choices::{Choice, ChoiceKey, ChoiceStore, Selection};
+pub use clean::{CleanedUrl, UrlCleaner};
+pub use config::{AppConfig, Config, CustomHandler, PickerConfig};
+pub use dbus::DBusActivator;
+pub use error::{EXIT_PROGRAM_NOT_FOUND, Error, ProgramRole};
+pub use handler::{
+    AppAction,
+    HandlerScan,
+    LaunchOptions,
+    Launcher,
+    UrlBatch,
+    UrlBatches,
+    UrlHandler,
+    UrlHandlerApp,
+};
+pub use index::HandlerIndex;
+pub use mimeapps::MimeApps;
+pub use profiles::{BrowserProfile, ProfileDiscovery};
+pub use redirect::{UnwrappedUrl, unwrap_redirects};
+pub use router::{Route, Router, Rule};
+pub use source::{AppSettings, DesktopEntries, HandlerRegistry, HandlerSource};
+pub use startup::{ActivationToken, TokenSource};
+pub use terminal::Terminal;
 pub use url::Url;
+pub use visibility::{ExcludedEntry, Exclusion, Visibility};
+pub use wrapper::LaunchWrapper;
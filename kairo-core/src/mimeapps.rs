@@ -0,0 +1,200 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use freedesktop_desktop_entry as fde;
+
+const MIMEAPPS_LIST: &str = "mimeapps.list";
+
+const DEFAULT_APPLICATIONS: &str = "Default Applications";
+const ADDED_ASSOCIATIONS: &str = "Added Associations";
+const REMOVED_ASSOCIATIONS: &str = "Removed Associations";
+
+/// The associations for a MIME type found in a single `mimeapps.list` file.
+#[derive(Clone, Debug, Default)]
+struct MimeAppsList {
+    defaults: BTreeMap<String, Vec<String>>,
+    added: BTreeMap<String, Vec<String>>,
+    removed: BTreeMap<String, Vec<String>>,
+}
+
+impl MimeAppsList {
+    fn from_path(path: &Path) -> Option<Self> {
+        let ini = match fde::GenericEntry::from_path(path) {
+            Ok(ini) => ini,
+            Err(e) => {
+                log::trace!("Failed to read {path:?}: {e}");
+                return None;
+            }
+        };
+        log::debug!("Loaded MIME associations from {path:?}");
+
+        let group = |name: &str| {
+            ini.group(name)
+                .map(|group| {
+                    group
+                        .0
+                        .iter()
+                        .map(|(mime, ids)| (mime.to_ascii_lowercase(), desktop_ids(ids)))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        Some(Self {
+            defaults: group(DEFAULT_APPLICATIONS),
+            added: group(ADDED_ASSOCIATIONS),
+            removed: group(REMOVED_ASSOCIATIONS),
+        })
+    }
+}
+
+/// Splits a list of desktop file IDs, dropping the `.desktop` suffix so they match app IDs.
+fn desktop_ids(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| id.strip_suffix(".desktop").unwrap_or(id).to_string())
+        .collect()
+}
+
+/// The applications associated with a MIME type, as resolved from all the `mimeapps.list` files.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Associations {
+    /// The preferred default apps, most important first. The first one that is installed is the
+    /// default.
+    pub defaults: Vec<String>,
+    /// Apps associated with the MIME type in addition to the ones declaring it, most important
+    /// first.
+    pub added: Vec<String>,
+    /// Apps that must not be associated with the MIME type, even if they declare it.
+    pub removed: Vec<String>,
+}
+
+impl Associations {
+    /// Checks if the app is associated with the MIME type, given whether it declares it in its
+    /// `MimeType` key.
+    pub fn is_associated(&self, appid: &str, declares_mime: bool) -> bool {
+        self.defaults.iter().any(|id| id == appid)
+            || self.added.iter().any(|id| id == appid)
+            || (declares_mime && !self.removed.iter().any(|id| id == appid))
+    }
+
+    /// Checks if the app is listed as a default or added association, as opposed to only
+    /// declaring the MIME type itself.
+    pub fn is_explicit(&self, appid: &str) -> bool {
+        self.defaults
+            .iter()
+            .chain(&self.added)
+            .any(|id| id == appid)
+    }
+
+    /// Finds the default app among the installed ones.
+    pub fn default_app<'a>(&'a self, installed: &[&str]) -> Option<&'a str> {
+        self.defaults
+            .iter()
+            .map(String::as_str)
+            .find(|id| installed.contains(id))
+    }
+
+    /// Sort key ordering the apps by preference: the default app first, then the added
+    /// associations, then everything else.
+    pub fn rank(&self, appid: &str, default: Option<&str>) -> usize {
+        if default == Some(appid) {
+            return 0;
+        }
+
+        match self.added.iter().position(|id| id == appid) {
+            Some(position) => position + 1,
+            None => usize::MAX,
+        }
+    }
+}
+
+/// The user's and system's preferred applications for each MIME type, following the
+/// [MIME Applications Associations](https://specifications.freedesktop.org/mime-apps-spec/latest/)
+/// specification.
+#[derive(Clone, Debug, Default)]
+pub struct MimeApps {
+    /// The lists found, most important first.
+    lists: Vec<MimeAppsList>,
+}
+
+impl MimeApps {
+    /// Loads the `mimeapps.list` files from the XDG config and data directories, including the
+    /// ones specific to the desktops in `$XDG_CURRENT_DESKTOP`.
+    pub fn load() -> Self {
+        let dirs = xdg::BaseDirectories::new();
+        let desktops = fde::current_desktop().unwrap_or_default();
+
+        let config_dirs = dirs
+            .get_config_home()
+            .into_iter()
+            .chain(dirs.get_config_dirs());
+        let data_dirs = dirs
+            .get_data_home()
+            .into_iter()
+            .chain(dirs.get_data_dirs())
+            .map(|dir| dir.join("applications"));
+
+        Self::from_paths(list_paths(config_dirs.chain(data_dirs), &desktops))
+    }
+
+    /// Loads the given `mimeapps.list` files, most important first. Missing files are skipped.
+    pub fn from_paths(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let lists = paths
+            .into_iter()
+            .filter_map(|path| MimeAppsList::from_path(&path))
+            .collect();
+
+        Self { lists }
+    }
+
+    /// Resolves the associations for the MIME type across all lists.
+    ///
+    /// Removed associations only apply to the apps added by less important lists, and to the
+    /// apps declaring the MIME type themselves.
+    pub fn associations(&self, mime: &str) -> Associations {
+        let mime = mime.to_ascii_lowercase();
+        let mut associations = Associations::default();
+
+        for list in &self.lists {
+            if let Some(defaults) = list.defaults.get(&mime) {
+                associations.defaults.extend(defaults.iter().cloned());
+            }
+
+            if let Some(added) = list.added.get(&mime) {
+                let added = added
+                    .iter()
+                    .filter(|id| !associations.removed.contains(id))
+                    .filter(|id| !associations.added.contains(id))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                associations.added.extend(added);
+            }
+
+            if let Some(removed) = list.removed.get(&mime) {
+                associations.removed.extend(removed.iter().cloned());
+            }
+        }
+
+        associations
+    }
+}
+
+/// Lists the `mimeapps.list` paths to check in each directory, desktop-specific ones first.
+pub fn list_paths(dirs: impl IntoIterator<Item = PathBuf>, desktops: &[String]) -> Vec<PathBuf> {
+    dirs.into_iter()
+        .flat_map(|dir| {
+            desktops
+                .iter()
+                .map(|desktop| {
+                    dir.join(format!("{}-{MIMEAPPS_LIST}", desktop.to_ascii_lowercase()))
+                })
+                .chain(std::iter::once(dir.join(MIMEAPPS_LIST)))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
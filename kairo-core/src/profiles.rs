@@ -0,0 +1,260 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use freedesktop_desktop_entry as fde;
+use serde::Deserialize;
+
+use crate::UrlHandlerApp;
+
+/// A browser profile found in the browser's configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BrowserProfile {
+    /// The identifier used to select the profile on the command line.
+    pub id: String,
+    /// The name displayed to the user.
+    pub name: String,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum BrowserFamily {
+    Firefox,
+    Chromium,
+}
+
+/// Where the browser stores its profiles, relative to the home or config directory.
+#[derive(Clone, Copy, Debug)]
+enum ProfilesDir {
+    Home(&'static str),
+    Config(&'static str),
+}
+
+struct Browser {
+    family: BrowserFamily,
+    desktop_ids: &'static [&'static str],
+    dirs: &'static [ProfilesDir],
+}
+
+const BROWSERS: &[Browser] = &[
+    Browser {
+        family: BrowserFamily::Firefox,
+        desktop_ids: &["firefox", "firefox-esr", "org.mozilla.firefox"],
+        dirs: &[
+            ProfilesDir::Config("mozilla/firefox"),
+            ProfilesDir::Home(".mozilla/firefox"),
+        ],
+    },
+    Browser {
+        family: BrowserFamily::Firefox,
+        desktop_ids: &["librewolf", "io.gitlab.librewolf-community"],
+        dirs: &[ProfilesDir::Home(".librewolf")],
+    },
+    Browser {
+        family: BrowserFamily::Chromium,
+        desktop_ids: &["google-chrome", "com.google.Chrome"],
+        dirs: &[ProfilesDir::Config("google-chrome")],
+    },
+    Browser {
+        family: BrowserFamily::Chromium,
+        desktop_ids: &["chromium", "chromium-browser", "org.chromium.Chromium"],
+        dirs: &[ProfilesDir::Config("chromium")],
+    },
+    Browser {
+        family: BrowserFamily::Chromium,
+        desktop_ids: &["brave-browser", "com.brave.Browser"],
+        dirs: &[ProfilesDir::Config("BraveSoftware/Brave-Browser")],
+    },
+    Browser {
+        family: BrowserFamily::Chromium,
+        desktop_ids: &["vivaldi-stable", "vivaldi", "com.vivaldi.Vivaldi"],
+        dirs: &[ProfilesDir::Config("vivaldi")],
+    },
+];
+
+/// Discovers the profiles of Firefox and Chromium-based browsers, exposing each of them as a
+/// separate handler.
+#[derive(Clone, Debug)]
+pub struct ProfileDiscovery {
+    home: PathBuf,
+    config_home: PathBuf,
+}
+
+impl ProfileDiscovery {
+    /// Creates a discovery that looks for profiles under the given home and config directories.
+    pub fn new(home: impl Into<PathBuf>, config_home: impl Into<PathBuf>) -> Self {
+        Self {
+            home: home.into(),
+            config_home: config_home.into(),
+        }
+    }
+
+    /// Creates a discovery using the user's home and `$XDG_CONFIG_HOME` directories.
+    pub fn from_env() -> Option<Self> {
+        let dirs = xdg::BaseDirectories::new();
+        let home = std::env::home_dir()?;
+        let config_home = dirs.get_config_home()?;
+
+        Some(Self::new(home, config_home))
+    }
+
+    /// Lists the profiles of the browser with the given app ID.
+    ///
+    /// The profiles of Flatpak apps, whose IDs are reverse DNS names such as
+    /// `org.mozilla.firefox`, are read from `~/.var/app/<appid>`.
+    pub fn profiles(&self, appid: &str) -> Vec<BrowserProfile> {
+        let Some(browser) = BROWSERS
+            .iter()
+            .find(|browser| browser.desktop_ids.contains(&appid))
+        else {
+            return vec![];
+        };
+
+        // Flatpak apps keep their data in a directory of their own, named after the app ID
+        let (home, config_home) = match appid.contains('.') {
+            true => {
+                let data = self.home.join(".var/app").join(appid);
+                (data.clone(), data.join("config"))
+            }
+            false => (self.home.clone(), self.config_home.clone()),
+        };
+
+        browser
+            .dirs
+            .iter()
+            .map(|dir| match dir {
+                ProfilesDir::Home(dir) => home.join(dir),
+                ProfilesDir::Config(dir) => config_home.join(dir),
+            })
+            .map(|dir| match browser.family {
+                BrowserFamily::Firefox => firefox_profiles(&dir.join("profiles.ini")),
+                BrowserFamily::Chromium => chromium_profiles(&dir.join("Local State")),
+            })
+            .find(|profiles| !profiles.is_empty())
+            .unwrap_or_default()
+    }
+
+    /// Creates a handler for each profile of the given browser app.
+    ///
+    /// Browsers with a single profile don't get any handler, as it would be the same as
+    /// launching the browser itself.
+    pub fn profile_handlers(&self, app: &UrlHandlerApp) -> Vec<UrlHandlerApp> {
+        let Some(browser) = BROWSERS
+            .iter()
+            .find(|browser| browser.desktop_ids.contains(&app.appid.as_str()))
+        else {
+            return vec![];
+        };
+
+        let profiles = self.profiles(&app.appid);
+        log::debug!("Found {} profiles for {}", profiles.len(), app.appid);
+
+        if profiles.len() < 2 {
+            return vec![];
+        }
+
+        profiles
+            .into_iter()
+            .map(|profile| {
+                let arg = match browser.family {
+                    BrowserFamily::Firefox => vec!["-P".to_string(), profile.id.clone()],
+                    BrowserFamily::Chromium => {
+                        vec![format!("--profile-directory={}", profile.id)]
+                    }
+                };
+
+                UrlHandlerApp {
+                    appid: format!("{}@{}", app.appid, slug(&profile.id)),
+                    name: format!("{} — {}", app.name, profile.name),
+                    args: [app.args.clone(), arg].concat(),
+                    is_default: false,
+                    ..app.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Adds the profile handlers right after the browser each of them belongs to.
+    pub fn with_profiles(&self, apps: Vec<UrlHandlerApp>) -> Vec<UrlHandlerApp> {
+        apps.into_iter()
+            .flat_map(|app| {
+                let profiles = self.profile_handlers(&app);
+                std::iter::once(app).chain(profiles)
+            })
+            .collect()
+    }
+}
+
+fn firefox_profiles(path: &Path) -> Vec<BrowserProfile> {
+    log::trace!("Reading Firefox profiles from {path:?}");
+
+    let ini = match fde::GenericEntry::from_path(path) {
+        Ok(ini) => ini,
+        Err(e) => {
+            log::trace!("Failed to read {path:?}: {e}");
+            return vec![];
+        }
+    };
+
+    ini.groups
+        .0
+        .iter()
+        .filter(|(group, _)| group.starts_with("Profile"))
+        .filter_map(|(_, profile)| profile.entry("Name"))
+        .map(|name| BrowserProfile {
+            id: name.to_string(),
+            name: name.to_string(),
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct LocalState {
+    #[serde(default)]
+    profile: LocalStateProfile,
+}
+
+#[derive(Deserialize, Default)]
+struct LocalStateProfile {
+    #[serde(default)]
+    info_cache: BTreeMap<String, ProfileInfo>,
+}
+
+#[derive(Deserialize)]
+struct ProfileInfo {
+    name: Option<String>,
+}
+
+fn chromium_profiles(path: &Path) -> Vec<BrowserProfile> {
+    log::trace!("Reading Chromium profiles from {path:?}");
+
+    let state = std::fs::read_to_string(path)
+        .map_err(|e| log::trace!("Failed to read {path:?}: {e}"))
+        .and_then(|content| {
+            serde_json::from_str::<LocalState>(&content)
+                .map_err(|e| log::warn!("Failed to parse {path:?}: {e}"))
+        });
+    let Ok(state) = state else {
+        return vec![];
+    };
+
+    state
+        .profile
+        .info_cache
+        .into_iter()
+        .map(|(dir, info)| BrowserProfile {
+            name: info.name.unwrap_or_else(|| dir.clone()),
+            id: dir,
+        })
+        .collect()
+}
+
+/// Turns a profile ID into something usable as part of an app ID (e.g. "Profile 1" → "profile-1").
+fn slug(id: &str) -> String {
+    id.chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() => c.to_ascii_lowercase(),
+            _ => '-',
+        })
+        .collect()
+}
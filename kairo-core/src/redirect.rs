@@ -0,0 +1,196 @@
+use url::Url;
+
+/// Stops unwrapping after this many nested redirectors, in case of a loop.
+const MAX_DEPTH: usize = 8;
+
+/// A known URL wrapper that encodes the real destination in one of its query parameters.
+struct Redirector {
+    name: &'static str,
+    /// Hosts of the wrapper URL. A leading `*.` stands for a single label, as in
+    /// `eur01.safelinks.protection.outlook.com`, and a trailing `.*` for a country domain, as in
+    /// `www.google.co.uk`.
+    hosts: &'static [&'static str],
+    /// Path of the wrapper URL, or its prefix when it ends with `*`.
+    path: &'static str,
+    /// Query parameters that may hold the destination, in order of preference.
+    params: &'static [&'static str],
+}
+
+const REDIRECTORS: &[Redirector] = &[
+    Redirector {
+        name: "Outlook Safe Links",
+        hosts: &["*.safelinks.protection.outlook.com"],
+        path: "/",
+        params: &["url"],
+    },
+    Redirector {
+        name: "Microsoft Defender Safe Links",
+        hosts: &["statics.teams.cdn.office.net"],
+        path: "/evergreen-assets/safelinks/*",
+        params: &["url"],
+    },
+    Redirector {
+        name: "Google",
+        hosts: &["www.google.*"],
+        path: "/url",
+        params: &["q", "url"],
+    },
+    Redirector {
+        name: "YouTube",
+        hosts: &["www.youtube.com", "m.youtube.com"],
+        path: "/redirect",
+        params: &["q"],
+    },
+    Redirector {
+        name: "Slack",
+        hosts: &["slack-redir.net"],
+        path: "/link",
+        params: &["url"],
+    },
+    Redirector {
+        name: "Facebook",
+        hosts: &["l.facebook.com", "lm.facebook.com"],
+        path: "/l.php",
+        params: &["u"],
+    },
+    Redirector {
+        name: "Messenger",
+        hosts: &["l.messenger.com"],
+        path: "/l.php",
+        params: &["u"],
+    },
+    Redirector {
+        name: "Instagram",
+        hosts: &["l.instagram.com"],
+        path: "/",
+        params: &["u"],
+    },
+    Redirector {
+        name: "LinkedIn",
+        hosts: &["www.linkedin.com"],
+        path: "/redir/redirect",
+        params: &["url"],
+    },
+    Redirector {
+        name: "Reddit",
+        hosts: &["out.reddit.com"],
+        path: "/*",
+        params: &["url"],
+    },
+    Redirector {
+        name: "Steam",
+        hosts: &["steamcommunity.com"],
+        path: "/linkfilter/",
+        params: &["url", "u"],
+    },
+    Redirector {
+        name: "DuckDuckGo",
+        hosts: &["duckduckgo.com"],
+        path: "/l/",
+        params: &["uddg"],
+    },
+    Redirector {
+        name: "VK",
+        hosts: &["away.vk.com"],
+        path: "/away.php",
+        params: &["to"],
+    },
+    Redirector {
+        name: "Tumblr",
+        hosts: &["t.umblr.com"],
+        path: "/redirect",
+        params: &["z"],
+    },
+];
+
+impl Redirector {
+    /// Extracts the destination from the URL, if it is wrapped by this redirector.
+    fn destination(&self, url: &Url) -> Option<Url> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        let path_matches = match self.path.strip_suffix('*') {
+            Some(prefix) => url.path().starts_with(prefix),
+            None => url.path() == self.path,
+        };
+        if !self
+            .hosts
+            .iter()
+            .any(|pattern| host_matches(pattern, &host))
+            || !path_matches
+        {
+            return None;
+        }
+
+        self.params.iter().find_map(|param| {
+            url.query_pairs()
+                .find(|(name, _)| name == param)
+                .and_then(|(_, value)| Url::parse(&value).ok())
+                .filter(|destination| matches!(destination.scheme(), "http" | "https"))
+        })
+    }
+}
+
+/// Matches a host against the pattern of a [Redirector].
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let is_label = |label: &str| {
+        !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+
+    if let Some(domain) = pattern.strip_prefix("*.") {
+        return host
+            .strip_suffix(domain)
+            .and_then(|subdomain| subdomain.strip_suffix('.'))
+            .is_some_and(is_label);
+    }
+    if let Some(name) = pattern.strip_suffix(".*") {
+        // A top-level domain, possibly under a second-level one (e.g. `co.uk` or `com.br`)
+        return host
+            .strip_prefix(name)
+            .and_then(|suffix| suffix.strip_prefix('.'))
+            .is_some_and(|suffix| match suffix.split_once('.') {
+                Some((second, top)) => matches!(second, "co" | "com") && is_label(top),
+                None => is_label(suffix),
+            });
+    }
+
+    pattern == host
+}
+
+/// The result of unwrapping a URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnwrappedUrl {
+    /// The real destination of the URL.
+    pub url: Url,
+    /// The names of the redirectors that were unwrapped, outermost first.
+    pub redirectors: Vec<&'static str>,
+}
+
+impl UnwrappedUrl {
+    /// Checks if the URL was wrapped by any redirector.
+    pub fn is_modified(&self) -> bool {
+        !self.redirectors.is_empty()
+    }
+}
+
+/// Replaces redirector and safe-link URLs with the destination encoded in them.
+///
+/// Only the URL itself is decoded and no request is made, so shorteners that require following
+/// the redirect (e.g. `t.co`) are left untouched. Nested wrappers are unwrapped recursively.
+pub fn unwrap_redirects(url: &Url) -> UnwrappedUrl {
+    let mut url = url.clone();
+    let mut redirectors = vec![];
+
+    while redirectors.len() < MAX_DEPTH {
+        let Some((redirector, destination)) = REDIRECTORS
+            .iter()
+            .find_map(|redirector| redirector.destination(&url).map(|dest| (redirector, dest)))
+        else {
+            break;
+        };
+
+        log::info!("Unwrapped {} link to {destination}", redirector.name);
+        redirectors.push(redirector.name);
+        url = destination;
+    }
+
+    UnwrappedUrl { url, redirectors }
+}
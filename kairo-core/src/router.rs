@@ -0,0 +1,291 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+use serde::Deserialize;
+use url::Url;
+
+use crate::{AppAction, Error, LaunchWrapper, Result, UrlHandlerApp, config::resolve_path};
+
+const RULES_FILE: &str = "rules.toml";
+
+/// A user rule that routes matching URLs to a specific handler.
+///
+/// Every condition that is set must match for the rule to apply. A rule without any condition
+/// matches all URLs, which is useful as a catch-all at the end of the list.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Optional name used to identify the rule in logs.
+    pub name: Option<String>,
+
+    /// The app ID of the handler that should open matching URLs.
+    pub handler: String,
+
+    /// Optional ID of the handler's desktop action to open matching URLs with.
+    pub action: Option<String>,
+
+    /// Matches the URL scheme (e.g. "https"), case-insensitive.
+    pub scheme: Option<String>,
+
+    /// Matches the exact URL host, case-insensitive.
+    pub host: Option<String>,
+
+    /// Matches the URL host against a glob pattern (e.g. "*.example.com"), case-insensitive.
+    pub host_glob: Option<String>,
+
+    /// Matches URLs whose path starts with the given prefix.
+    pub path_prefix: Option<String>,
+
+    /// Matches the full URL against a regular expression.
+    #[serde(default, with = "regex_serde")]
+    pub regex: Option<Regex>,
+
+    /// Matches query parameters by name. Values are glob patterns, so "*" only requires the
+    /// parameter to be present.
+    #[serde(default)]
+    pub query: BTreeMap<String, String>,
+
+    /// Environment variables set for the handler when it opens matching URLs.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+
+    /// The directory the handler runs in when it opens matching URLs.
+    pub working_dir: Option<PathBuf>,
+
+    /// The command wrapping the handler when it opens matching URLs (e.g. "firejail"), instead
+    /// of the one from the configuration.
+    pub wrapper: Option<LaunchWrapper>,
+}
+
+impl Rule {
+    /// Checks if the given URL satisfies all the conditions of this rule.
+    pub fn matches(&self, url: &Url) -> bool {
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+
+        self.scheme
+            .as_ref()
+            .is_none_or(|scheme| scheme.eq_ignore_ascii_case(url.scheme()))
+            && self
+                .host
+                .as_ref()
+                .is_none_or(|expected| expected.eq_ignore_ascii_case(&host))
+            && self
+                .host_glob
+                .as_ref()
+                .is_none_or(|pattern| glob_match(&pattern.to_ascii_lowercase(), &host))
+            && self
+                .path_prefix
+                .as_ref()
+                .is_none_or(|prefix| url.path().starts_with(prefix.as_str()))
+            && self
+                .regex
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(url.as_str()))
+            && self.query.iter().all(|(key, pattern)| {
+                url.query_pairs()
+                    .any(|(k, v)| k == key.as_str() && glob_match(pattern, &v))
+            })
+    }
+
+    /// A human readable label for the rule, used in logs.
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.handler)
+    }
+}
+
+/// A URL resolved to a handler by one of the routing rules.
+#[derive(Clone, Debug)]
+pub struct Route<'r, 'a> {
+    pub rule: &'r Rule,
+    pub app: &'a UrlHandlerApp,
+    pub action: Option<&'a AppAction>,
+}
+
+impl Route<'_, '_> {
+    /// The app to launch, with the environment variables, working directory and wrapper of the
+    /// rule.
+    pub fn handler(&self) -> UrlHandlerApp {
+        let mut app = self
+            .app
+            .with_env(&self.rule.env, self.rule.working_dir.as_deref());
+        if let Some(wrapper) = &self.rule.wrapper {
+            app.wrapper = Some(wrapper.clone());
+        }
+
+        app
+    }
+}
+
+/// Resolves URLs to handlers automatically based on an ordered list of rules.
+#[derive(Clone, Debug, Default)]
+pub struct Router {
+    rules: Vec<Rule>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+impl Router {
+    /// Creates a router evaluating the given rules in order.
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self { rules }
+    }
+
+    /// Loads the rules from `$XDG_CONFIG_HOME/kairo/rules.toml`.
+    ///
+    /// Returns a router without rules if the file does not exist.
+    pub fn load() -> Result<Self> {
+        match xdg::BaseDirectories::with_prefix("kairo").find_config_file(RULES_FILE) {
+            Some(path) => Self::from_file(&path),
+            None => {
+                log::debug!("No routing rules file found");
+                Ok(Self::default())
+            }
+        }
+    }
+
+    /// Loads the rules from `rules.toml` in the given directory, or from the default location if
+    /// `None`.
+    pub fn load_from(dir: Option<&Path>) -> Result<Self> {
+        let Some(dir) = dir else {
+            return Self::load();
+        };
+
+        let path = dir.join(RULES_FILE);
+        match path.is_file() {
+            true => Self::from_file(&path),
+            false => {
+                log::debug!("No routing rules file found in {dir:?}");
+                Ok(Self::default())
+            }
+        }
+    }
+
+    /// Loads the rules from a TOML file containing a list of `[[rules]]` tables.
+    ///
+    /// Relative working directories are resolved against the directory of the file.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        log::debug!("Loading routing rules from {path:?}");
+
+        let content = std::fs::read_to_string(path)?;
+        let mut file: RulesFile = toml::from_str(&content).map_err(|source| Error::LoadRules {
+            path: path.into(),
+            source,
+        })?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        file.rules
+            .iter_mut()
+            .filter_map(|rule| rule.working_dir.as_mut())
+            .for_each(|dir| resolve_path(dir, base));
+
+        Ok(Self::new(file.rules))
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Finds the first rule that matches the URL and points to one of the given apps.
+    ///
+    /// Rules referencing handlers or actions that are not in `apps` are skipped, so the next
+    /// matching rule gets a chance to resolve the URL.
+    pub fn route<'r, 'a>(&'r self, url: &Url, apps: &'a [UrlHandlerApp]) -> Option<Route<'r, 'a>> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches(url))
+            .find_map(|rule| {
+                let Some(app) = apps.iter().find(|app| app.appid == rule.handler) else {
+                    log::warn!(
+                        "Rule '{}' matched {url} but handler '{}' is not available",
+                        rule.label(),
+                        rule.handler
+                    );
+                    return None;
+                };
+
+                match &rule.action {
+                    Some(id) => match app.action(id) {
+                        Some(action) => Some(Route {
+                            rule,
+                            app,
+                            action: Some(action),
+                        }),
+                        None => {
+                            log::warn!(
+                                "Rule '{}' matched {url} but handler '{}' has no action '{id}'",
+                                rule.label(),
+                                rule.handler
+                            );
+                            None
+                        }
+                    },
+                    None => Some(Route {
+                        rule,
+                        app,
+                        action: None,
+                    }),
+                }
+            })
+            .inspect(|route| {
+                log::info!(
+                    "Routing {url} to {} using rule '{}'",
+                    route.app.appid,
+                    route.rule.label()
+                )
+            })
+    }
+}
+
+/// Matches `text` against a glob `pattern`, where `*` matches any sequence of characters and `?`
+/// matches a single character.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+mod regex_serde {
+    use regex::Regex;
+    use serde::{Deserialize, Deserializer, de::Error};
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|pattern| Regex::new(&pattern).map_err(D::Error::custom))
+            .transpose()
+    }
+}
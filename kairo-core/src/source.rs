@@ -0,0 +1,163 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use crate::{
+    AppConfig,
+    Config,
+    Error,
+    HandlerIndex,
+    MimeApps,
+    ProfileDiscovery,
+    Result,
+    UrlHandlerApp,
+    Visibility,
+};
+
+/// Discovers the handlers of URL schemes, such as the installed applications.
+///
+/// Sources are chained in a [HandlerRegistry], each one receiving the handlers found by the
+/// previous ones, so they can add, replace or expand them.
+pub trait HandlerSource {
+    /// Adds the handlers for the scheme to the ones found so far.
+    fn discover(&self, scheme: &str, apps: &mut Vec<UrlHandlerApp>) -> Result<()>;
+}
+
+/// The applications installed as desktop entries.
+#[derive(Clone, Debug, Default)]
+pub struct DesktopEntries {
+    /// Locales for the names of the applications. If `None`, the system's locales are used.
+    pub locales: Option<Vec<String>>,
+    /// Paths to search for desktop entries. If `None`, the XDG data directories are used.
+    pub search_paths: Option<Vec<PathBuf>>,
+    /// Associations between the schemes and the applications. If `None`, the user's
+    /// `mimeapps.list` files are loaded.
+    pub mime_apps: Option<MimeApps>,
+    /// Visibility rules for the entries. If `None`, the ones of the current desktop and `PATH`
+    /// are used.
+    pub visibility: Option<Visibility>,
+    /// Where the handler index is cached. If `None`, the index is only kept in memory and
+    /// rebuilt on each scan.
+    pub index_path: Option<PathBuf>,
+}
+
+impl HandlerSource for DesktopEntries {
+    fn discover(&self, scheme: &str, apps: &mut Vec<UrlHandlerApp>) -> Result<()> {
+        let mime_apps = self.mime_apps.clone().unwrap_or_else(MimeApps::load);
+        let visibility = self.visibility.clone().unwrap_or_else(Visibility::from_env);
+        let mut index = match &self.index_path {
+            Some(path) => HandlerIndex::open(path),
+            None => HandlerIndex::in_memory(),
+        };
+
+        let scan = UrlHandlerApp::scan_scheme_with_index(
+            scheme,
+            self.locales.clone(),
+            self.search_paths.clone(),
+            &mime_apps,
+            &visibility,
+            &mut index,
+        )?;
+        apps.extend(scan.apps);
+
+        Ok(())
+    }
+}
+
+/// The handlers defined in the configuration, which replace the apps with the same ID.
+impl HandlerSource for Config {
+    fn discover(&self, scheme: &str, apps: &mut Vec<UrlHandlerApp>) -> Result<()> {
+        *apps = self.with_custom_handlers(scheme, std::mem::take(apps));
+        Ok(())
+    }
+}
+
+/// The launch settings of the apps from the configuration, applied to the apps found so far.
+///
+/// Added after the other sources so the settings reach the browser profiles too. A profile app,
+/// such as `firefox@work`, gets the settings of its browser and then its own.
+#[derive(Clone, Debug, Default)]
+pub struct AppSettings {
+    /// The settings of each app, by app ID.
+    pub apps: BTreeMap<String, AppConfig>,
+}
+
+impl HandlerSource for AppSettings {
+    fn discover(&self, _scheme: &str, apps: &mut Vec<UrlHandlerApp>) -> Result<()> {
+        for app in apps.iter_mut() {
+            let browser = app.appid.split_once('@').map(|(browser, _)| browser);
+            let settings: Vec<&AppConfig> = browser
+                .into_iter()
+                .chain([app.appid.as_str()])
+                .filter_map(|appid| self.apps.get(appid))
+                .collect();
+            for settings in settings {
+                *app = app.with_env(&settings.env, settings.working_dir.as_deref());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The profiles of the browsers found so far, listed after each browser.
+impl HandlerSource for ProfileDiscovery {
+    fn discover(&self, _scheme: &str, apps: &mut Vec<UrlHandlerApp>) -> Result<()> {
+        *apps = self.with_profiles(std::mem::take(apps));
+        Ok(())
+    }
+}
+
+/// Finds the handlers of URL schemes from several sources, in order.
+#[derive(Default)]
+pub struct HandlerRegistry {
+    sources: Vec<Box<dyn HandlerSource>>,
+}
+
+impl HandlerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the registry with the built-in sources: the desktop entries in the configured
+    /// search paths, the custom handlers from the configuration, the browser profiles, and the
+    /// launch settings of the apps from the configuration.
+    pub fn from_config(config: &Config) -> Self {
+        let mut registry = Self::new()
+            .with_source(DesktopEntries {
+                locales: config.locales.clone(),
+                search_paths: config.search_paths.clone(),
+                mime_apps: None,
+                visibility: None,
+                index_path: HandlerIndex::default_path()
+                    .inspect_err(|e| log::warn!("Failed to open the handler index: {e}"))
+                    .ok(),
+            })
+            .with_source(config.clone());
+        if let Some(discovery) = ProfileDiscovery::from_env() {
+            registry = registry.with_source(discovery);
+        }
+
+        registry.with_source(AppSettings {
+            apps: config.apps.clone(),
+        })
+    }
+
+    /// Adds a source, which receives the handlers found by the ones added before.
+    pub fn with_source(mut self, source: impl HandlerSource + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// Finds the handlers for the scheme in all the sources.
+    pub fn handlers_for_scheme(&self, scheme: &str) -> Result<Vec<UrlHandlerApp>> {
+        let mut apps = vec![];
+        for source in &self.sources {
+            source.discover(scheme, &mut apps)?;
+        }
+
+        if apps.is_empty() {
+            return Err(Error::NoHandlersFound(scheme.to_string()));
+        }
+
+        Ok(apps)
+    }
+}
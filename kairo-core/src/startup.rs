@@ -0,0 +1,93 @@
+use std::process::Command;
+
+/// The environment variable holding the activation token on Wayland.
+pub const XDG_ACTIVATION_TOKEN: &str = "XDG_ACTIVATION_TOKEN";
+
+/// The environment variable holding the startup notification ID on X11.
+pub const DESKTOP_STARTUP_ID: &str = "DESKTOP_STARTUP_ID";
+
+/// A token that lets a launched application take the focus and the launcher show startup
+/// feedback, as defined by the [xdg-activation](https://wayland.app/protocols/xdg-activation-v1)
+/// protocol on Wayland and the
+/// [Startup Notification](https://specifications.freedesktop.org/startup-notification-spec/latest/)
+/// specification on X11.
+///
+/// Compositors only hand the focus to applications started with a valid token, so the token Kairo
+/// was launched with is forwarded to the application that opens the URLs. Tokens can only be used
+/// once, and a launcher with a window of its own, such as the desktop picker, can request a fresh
+/// one from the compositor and pass it in [LaunchOptions](crate::LaunchOptions) instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActivationToken {
+    token: String,
+    source: TokenSource,
+}
+
+/// The windowing system an [ActivationToken] was issued for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenSource {
+    /// An xdg-activation token from the Wayland compositor.
+    Wayland,
+    /// A startup notification ID on X11.
+    X11,
+}
+
+impl TokenSource {
+    /// The environment variable applications read the token from.
+    pub fn variable(self) -> &'static str {
+        match self {
+            TokenSource::Wayland => XDG_ACTIVATION_TOKEN,
+            TokenSource::X11 => DESKTOP_STARTUP_ID,
+        }
+    }
+}
+
+impl ActivationToken {
+    pub fn new(token: impl Into<String>, source: TokenSource) -> Self {
+        Self {
+            token: token.into(),
+            source,
+        }
+    }
+
+    /// An xdg-activation token, from the Wayland compositor.
+    pub fn wayland(token: impl Into<String>) -> Self {
+        Self::new(token, TokenSource::Wayland)
+    }
+
+    /// A startup notification ID, on X11.
+    pub fn x11(token: impl Into<String>) -> Self {
+        Self::new(token, TokenSource::X11)
+    }
+
+    /// Reads the token Kairo was launched with from `$XDG_ACTIVATION_TOKEN`, or from
+    /// `$DESKTOP_STARTUP_ID` on X11.
+    pub fn from_env() -> Option<Self> {
+        [TokenSource::Wayland, TokenSource::X11]
+            .into_iter()
+            .filter_map(|source| Some((std::env::var(source.variable()).ok()?, source)))
+            .find(|(token, _)| !token.is_empty())
+            .map(|(token, source)| Self::new(token, source))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.token
+    }
+
+    pub fn source(&self) -> TokenSource {
+        self.source
+    }
+
+    /// Passes the token to the command in the environment variable of its windowing system,
+    /// removing the other one so the command doesn't inherit a stale token from Kairo.
+    pub fn set_env(&self, command: &mut Command) {
+        Self::remove_env(command);
+        command.env(self.source.variable(), &self.token);
+    }
+
+    /// Keeps the command from inheriting the token Kairo was launched with.
+    pub fn remove_env(command: &mut Command) {
+        command
+            .env_remove(XDG_ACTIVATION_TOKEN)
+            .env_remove(DESKTOP_STARTUP_ID);
+    }
+}
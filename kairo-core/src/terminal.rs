@@ -0,0 +1,107 @@
+use std::{ffi::OsStr, path::Path};
+
+use crate::{
+    Error,
+    Result,
+    exec::{self, COMMAND_PLACEHOLDER},
+};
+
+/// Launcher from the [xdg-terminal-exec](https://github.com/Vladimir-csp/xdg-terminal-exec)
+/// proposal, which runs a command in the user's preferred terminal.
+const XDG_TERMINAL_EXEC: &str = "xdg-terminal-exec";
+
+/// Common terminal emulators and the arguments that make them run a command.
+const KNOWN_TERMINALS: &[(&str, &[&str])] = &[
+    ("kitty", &["--"]),
+    ("foot", &[]),
+    ("alacritty", &["-e"]),
+    ("wezterm", &["start", "--"]),
+    ("ghostty", &["-e"]),
+    ("ptyxis", &["--"]),
+    ("kgx", &["--"]),
+    ("gnome-terminal", &["--"]),
+    ("konsole", &["-e"]),
+    ("xfce4-terminal", &["-x"]),
+    ("terminator", &["-x"]),
+    ("urxvt", &["-e"]),
+    ("st", &["-e"]),
+    ("xterm", &["-e"]),
+];
+
+/// A terminal emulator used to launch handlers that set `Terminal=true`, such as `w3m` or
+/// `neomutt`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Terminal {
+    template: Vec<String>,
+}
+
+impl Terminal {
+    /// Creates a terminal from a command template, such as `kitty --single-instance {command}`.
+    ///
+    /// The handler's command is spliced in as done by [exec::wrap_command].
+    pub fn from_template(template: &str) -> Result<Self> {
+        let template = exec::split(template)
+            .ok()
+            .filter(|args| !args.is_empty())
+            .ok_or_else(|| Error::InvalidTerminalTemplate(template.to_string()))?;
+
+        Ok(Self { template })
+    }
+
+    /// Detects the terminal to use from the current environment.
+    pub fn detect() -> Option<Self> {
+        let paths = std::env::var_os("PATH").unwrap_or_default();
+        let terminal = std::env::var("TERMINAL").ok();
+
+        Self::detect_from(&paths, terminal.as_deref())
+    }
+
+    /// Detects the terminal to use, looking for programs in the given `PATH`-like list.
+    ///
+    /// `xdg-terminal-exec` is preferred, followed by the `$TERMINAL` value (either a program,
+    /// possibly with arguments, or a template) and then the first common terminal emulator found.
+    pub fn detect_from(paths: &OsStr, terminal: Option<&str>) -> Option<Self> {
+        let known = |program: &str| {
+            KNOWN_TERMINALS
+                .iter()
+                .find(|(name, _)| *name == program)
+                .map(|(_, args)| *args)
+        };
+        let with_args = |program: &str, args: &[&str]| Self {
+            template: std::iter::once(program)
+                .chain(args.iter().copied())
+                .map(str::to_string)
+                .collect(),
+        };
+
+        if exec::find_program_in(XDG_TERMINAL_EXEC, paths).is_some() {
+            log::debug!("Using {XDG_TERMINAL_EXEC} as terminal");
+            return Some(with_args(XDG_TERMINAL_EXEC, &[]));
+        }
+
+        if let Some(terminal) = terminal.filter(|terminal| !terminal.trim().is_empty()) {
+            log::debug!("Using $TERMINAL={terminal} as terminal");
+            if terminal.contains(COMMAND_PLACEHOLDER) {
+                return Self::from_template(terminal).ok();
+            }
+
+            // The value may hold arguments too, as in `kitty --single-instance`
+            let mut template = exec::split(terminal).ok()?;
+            let program = Path::new(template.first()?).file_name()?.to_str()?;
+            let args = known(program).unwrap_or(&["-e"]);
+            template.extend(args.iter().map(|arg| arg.to_string()));
+            return Some(Self { template });
+        }
+
+        KNOWN_TERMINALS
+            .iter()
+            .find(|(program, _)| exec::find_program_in(program, paths).is_some())
+            .inspect(|(program, _)| log::debug!("Using {program} as terminal"))
+            .map(|(program, args)| with_args(program, args))
+    }
+
+    /// Wraps the command so it runs inside this terminal.
+    pub fn command(&self, cmd: String, args: Vec<String>) -> (String, Vec<String>) {
+        exec::wrap_command(&self.template, cmd, args, str::to_string)
+    }
+}
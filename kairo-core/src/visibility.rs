@@ -0,0 +1,120 @@
+use std::{ffi::OsString, fmt, path::PathBuf};
+
+use freedesktop_desktop_entry as fde;
+
+use crate::exec;
+
+/// Why a desktop entry associated with a scheme is not listed as one of its handlers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Exclusion {
+    /// The entry sets `Hidden=true`, which means it was deleted.
+    Hidden,
+    /// The entry sets `NoDisplay=true` and is not explicitly associated in `mimeapps.list`.
+    NoDisplay,
+    /// The program in the `TryExec` key is not installed.
+    TryExecNotFound(String),
+    /// The current desktop is not in the `OnlyShowIn` list.
+    OnlyShowIn(Vec<String>),
+    /// The current desktop is in the `NotShowIn` list.
+    NotShowIn(Vec<String>),
+    /// The association was removed in `mimeapps.list`.
+    RemovedAssociation,
+    /// The entry only accepts local files, but the scheme is not `file`.
+    FilesOnly,
+}
+
+impl fmt::Display for Exclusion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exclusion::Hidden => write!(f, "hidden"),
+            Exclusion::NoDisplay => write!(f, "not displayed"),
+            Exclusion::TryExecNotFound(program) => write!(f, "'{program}' is not installed"),
+            Exclusion::OnlyShowIn(desktops) => write!(f, "only shown in {}", desktops.join(", ")),
+            Exclusion::NotShowIn(desktops) => write!(f, "not shown in {}", desktops.join(", ")),
+            Exclusion::RemovedAssociation => write!(f, "association removed in mimeapps.list"),
+            Exclusion::FilesOnly => write!(f, "only accepts local files"),
+        }
+    }
+}
+
+/// Exclusions are serialized as the reason shown to users.
+impl serde::Serialize for Exclusion {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A desktop entry that was left out of the handlers, with the reason why.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct ExcludedEntry {
+    pub appid: String,
+    pub path: PathBuf,
+    pub reason: Exclusion,
+}
+
+/// Applies the visibility rules of the Desktop Entry Specification for the current desktop and
+/// the programs installed in `PATH`.
+#[derive(Clone, Debug, Default)]
+pub struct Visibility {
+    /// The current desktops, lowercased.
+    desktops: Vec<String>,
+    path: OsString,
+}
+
+impl Visibility {
+    /// Creates the rules for the given desktops (as in `$XDG_CURRENT_DESKTOP`) and `PATH`-like
+    /// list of directories.
+    pub fn new(desktops: &[&str], path: impl Into<OsString>) -> Self {
+        Self {
+            desktops: desktops.iter().map(|d| d.to_ascii_lowercase()).collect(),
+            path: path.into(),
+        }
+    }
+
+    /// Creates the rules from `$XDG_CURRENT_DESKTOP` and `$PATH`.
+    pub fn from_env() -> Self {
+        Self {
+            desktops: fde::current_desktop().unwrap_or_default(),
+            path: std::env::var_os("PATH").unwrap_or_default(),
+        }
+    }
+
+    /// Checks if the entry should be shown, returning why it should not otherwise.
+    pub fn check(&self, de: &fde::DesktopEntry) -> Result<(), Exclusion> {
+        if de.hidden() {
+            return Err(Exclusion::Hidden);
+        }
+
+        let in_current_desktop = |desktops: &[&str]| {
+            desktops
+                .iter()
+                .any(|desktop| self.desktops.contains(&desktop.to_ascii_lowercase()))
+        };
+        let to_vec = |desktops: Vec<&str>| {
+            desktops
+                .into_iter()
+                .filter(|desktop| !desktop.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+
+        if let Some(desktops) = de.only_show_in().filter(|d| !in_current_desktop(d)) {
+            return Err(Exclusion::OnlyShowIn(to_vec(desktops)));
+        }
+        if let Some(desktops) = de.not_show_in().filter(|d| in_current_desktop(d)) {
+            return Err(Exclusion::NotShowIn(to_vec(desktops)));
+        }
+
+        if let Some(program) = de.try_exec()
+            && exec::find_program_in(program, &self.path).is_none()
+        {
+            return Err(Exclusion::TryExecNotFound(program.to_string()));
+        }
+
+        if de.no_display() {
+            return Err(Exclusion::NoDisplay);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Error, Result, exec};
+
+/// Replaced by the app ID of the handler in wrapper templates.
+const APPID_PLACEHOLDER: &str = "{appid}";
+
+/// Replaced by the path to the desktop file of the handler in wrapper templates.
+const DESKTOP_FILE_PLACEHOLDER: &str = "{desktop_file}";
+
+/// Replaced by a unique systemd unit name for the launch in wrapper templates.
+const UNIT_PLACEHOLDER: &str = "{unit}";
+
+/// Replaced by the home directory in wrapper templates.
+const HOME_PLACEHOLDER: &str = "{home}";
+
+/// Replaced by `$XDG_RUNTIME_DIR` in wrapper templates.
+const RUNTIME_DIR_PLACEHOLDER: &str = "{runtime_dir}";
+
+/// Replaced by the path to the socket of the Wayland compositor in wrapper templates.
+const WAYLAND_SOCKET_PLACEHOLDER: &str = "{wayland_socket}";
+
+/// Built-in wrappers, by name.
+const PRESETS: &[(&str, &str)] = &[
+    (
+        "systemd-run",
+        "systemd-run --user --scope --quiet --collect --unit={unit} -- {command}",
+    ),
+    ("app2unit", "app2unit -t scope -u {unit} -- {command}"),
+    ("uwsm", "uwsm app -u {unit} -- {command}"),
+    ("firejail", "firejail --quiet -- {command}"),
+    (
+        "bwrap",
+        "bwrap --ro-bind / / --dev /dev --proc /proc --tmpfs /tmp \
+         --ro-bind-try /tmp/.X11-unix /tmp/.X11-unix --bind-try {home} {home} \
+         --bind-try {runtime_dir} {runtime_dir} --bind-try {wayland_socket} {wayland_socket} \
+         --unshare-all --share-net --die-with-parent -- {command}",
+    ),
+];
+
+/// A command that wraps the launched handlers, such as `systemd-run` to place them in a
+/// transient scope of their own or `firejail` to sandbox them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LaunchWrapper {
+    template: Vec<String>,
+}
+
+impl LaunchWrapper {
+    /// Creates a wrapper from a command template, such as `systemd-run --user --scope -- {command}`.
+    ///
+    /// The handler's command is spliced in as done by [exec::wrap_command]. Arguments can also
+    /// contain the `{appid}`, `{desktop_file}` and `{unit}` placeholders, the latter being a
+    /// unique name for a systemd scope following the
+    /// [desktop environment conventions](https://systemd.io/DESKTOP_ENVIRONMENTS/), as well as
+    /// `{home}`, `{runtime_dir}` and `{wayland_socket}` for the paths a sandbox has to expose.
+    pub fn from_template(template: &str) -> Result<Self> {
+        let template = exec::split(template)
+            .ok()
+            .filter(|args| !args.is_empty())
+            .ok_or_else(|| Error::InvalidWrapperTemplate(template.to_string()))?;
+
+        Ok(Self { template })
+    }
+
+    /// The built-in wrapper with the given name: `systemd-run`, `app2unit`, `uwsm`, `firejail` or
+    /// `bwrap`.
+    pub fn preset(name: &str) -> Option<Self> {
+        PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .and_then(|(_, template)| Self::from_template(template).ok())
+    }
+
+    /// Creates a wrapper from the name of a built-in one, or from a command template.
+    pub fn parse(value: &str) -> Result<Self> {
+        match Self::preset(value.trim()) {
+            Some(wrapper) => Ok(wrapper),
+            None => Self::from_template(value),
+        }
+    }
+
+    /// Wraps the command launching the handler with the given app ID and desktop file.
+    pub fn command(
+        &self,
+        appid: &str,
+        desktop_file: &Path,
+        cmd: String,
+        args: Vec<String>,
+    ) -> (String, Vec<String>) {
+        let unit = unit_name(appid);
+        let home = std::env::var("HOME").unwrap_or_default();
+        let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_default();
+        let wayland_socket = wayland_socket(&runtime_dir);
+        let expand = |arg: &str| {
+            arg.replace(APPID_PLACEHOLDER, appid)
+                .replace(DESKTOP_FILE_PLACEHOLDER, &desktop_file.to_string_lossy())
+                .replace(UNIT_PLACEHOLDER, &unit)
+                .replace(HOME_PLACEHOLDER, &home)
+                .replace(RUNTIME_DIR_PLACEHOLDER, &runtime_dir)
+                .replace(
+                    WAYLAND_SOCKET_PLACEHOLDER,
+                    &wayland_socket.to_string_lossy(),
+                )
+        };
+
+        exec::wrap_command(&self.template, cmd, args, expand)
+    }
+}
+
+/// Wrappers are read from the configuration as a preset name or a command template.
+impl<'de> serde::Deserialize<'de> for LaunchWrapper {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Self::parse(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// The socket of the Wayland compositor, `$WAYLAND_DISPLAY` relative to the runtime directory
+/// unless it is an absolute path.
+pub fn wayland_socket(runtime_dir: &str) -> PathBuf {
+    let display = std::env::var("WAYLAND_DISPLAY")
+        .ok()
+        .filter(|display| !display.is_empty())
+        .unwrap_or_else(|| "wayland-0".to_string());
+
+    Path::new(runtime_dir).join(display)
+}
+
+/// A unique name for the scope of a launch, as `app-kairo-<appid>-<random>.scope`.
+pub fn unit_name(appid: &str) -> String {
+    let random = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default()
+        ^ std::process::id();
+
+    format!("app-kairo-{}-{random:08x}.scope", escape_unit_name(appid))
+}
+
+/// Escapes the characters that are not allowed in unit names, like `systemd-escape` does.
+fn escape_unit_name(name: &str) -> String {
+    name.bytes()
+        .enumerate()
+        .map(|(i, byte)| match byte {
+            b'.' if i == 0 => format!("\\x{byte:02x}"),
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b':' | b'_' | b'.' => {
+                (byte as char).to_string()
+            }
+            _ => format!("\\x{byte:02x}"),
+        })
+        .collect()
+}
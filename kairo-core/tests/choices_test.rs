@@ -0,0 +1,183 @@
+mod utils;
+
+use kairo_core::{Choice, ChoiceKey, ChoiceStore, Error, MimeApps, Selection, Url};
+
+fn choice(handler: &str, always: bool) -> Choice {
+    Choice {
+        handler: handler.into(),
+        action: None,
+        always,
+    }
+}
+
+#[test]
+fn test_open_missing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = ChoiceStore::open(dir.path().join("choices.toml")).unwrap();
+
+    assert_eq!(store.iter().count(), 0);
+}
+
+#[test]
+fn test_remember_and_save() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nested/choices.toml");
+
+    let mut store = ChoiceStore::open(&path).unwrap();
+    store.remember(
+        ChoiceKey::Host("gitlab.work.com".into()),
+        choice("brave-browser", true),
+    );
+    store.remember(
+        ChoiceKey::Scheme("mailto".into()),
+        choice("thunderbird", false),
+    );
+    store.save().unwrap();
+
+    let store = ChoiceStore::open(&path).unwrap();
+    let choices = store.iter().collect::<Vec<_>>();
+    assert_eq!(
+        choices,
+        vec![
+            (
+                ChoiceKey::Host("gitlab.work.com".into()),
+                &choice("brave-browser", true)
+            ),
+            (
+                ChoiceKey::Scheme("mailto".into()),
+                &choice("thunderbird", false)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_lookup() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = ChoiceStore::open(dir.path().join("choices.toml")).unwrap();
+    store.remember(
+        ChoiceKey::Host("github.com".into()),
+        choice("firefox", false),
+    );
+    store.remember(
+        ChoiceKey::Scheme("https".into()),
+        choice("brave-browser", false),
+    );
+    store.remember(
+        ChoiceKey::Scheme("mailto".into()),
+        choice("thunderbird", true),
+    );
+
+    let lookup = |url: &str| {
+        let url: Url = url.parse().unwrap();
+        store
+            .lookup(&url)
+            .map(|(key, choice)| (key, choice.handler.clone()))
+    };
+
+    assert_eq!(
+        lookup("https://GitHub.com/aelesbao/kairo"),
+        Some((ChoiceKey::Host("github.com".into()), "firefox".into()))
+    );
+    assert_eq!(
+        lookup("https://example.com"),
+        Some((ChoiceKey::Scheme("https".into()), "brave-browser".into()))
+    );
+    assert_eq!(
+        lookup("mailto:someone@example.com"),
+        Some((ChoiceKey::Scheme("mailto".into()), "thunderbird".into()))
+    );
+    assert_eq!(lookup("ipfs://bafy"), None);
+}
+
+#[test]
+fn test_select() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = ChoiceStore::open(dir.path().join("choices.toml")).unwrap();
+    store.remember(
+        ChoiceKey::Host("github.com".into()),
+        choice("firefox", false),
+    );
+    store.remember(
+        ChoiceKey::Host("gitlab.com".into()),
+        choice("firefox", true),
+    );
+
+    let apps = utils::handlers_for_scheme("http", &MimeApps::default()).unwrap();
+    let targets: Vec<_> = apps
+        .iter()
+        .flat_map(|app| app.launch_targets().map(move |action| (app, action)))
+        .collect();
+    let firefox = targets
+        .iter()
+        .position(|(app, action)| app.appid == "firefox" && action.is_none())
+        .unwrap();
+    let select = |url: &str, targets: &[_], no_prompt| {
+        let url: Url = url.parse().unwrap();
+        store.select(&url, targets, no_prompt)
+    };
+
+    // Choices marked to always be used skip the prompt, others are preselected in it
+    assert_eq!(
+        select("https://gitlab.com", &targets, false),
+        Selection::Remembered(firefox)
+    );
+    assert_eq!(
+        select("https://github.com", &targets, false),
+        Selection::Prompt(firefox)
+    );
+    assert_eq!(
+        select("https://example.com", &targets, false),
+        Selection::Prompt(0)
+    );
+
+    // Without prompting, the remembered choice is still used before the first target
+    assert_eq!(
+        select("https://github.com", &targets, true),
+        Selection::Remembered(firefox)
+    );
+    assert_eq!(
+        select("https://example.com", &targets, true),
+        Selection::First
+    );
+
+    // A single app is opened without prompting, with its remembered action if any
+    let single: Vec<_> = targets
+        .iter()
+        .filter(|(app, _)| app.appid == "firefox")
+        .copied()
+        .collect();
+    assert!(single.len() > 1);
+    assert_eq!(
+        select("https://github.com", &single, false),
+        Selection::Remembered(0)
+    );
+    assert_eq!(
+        select("https://example.com", &single, false),
+        Selection::First
+    );
+    assert_eq!(Selection::Remembered(firefox).reason(), "remembered choice");
+    assert_eq!(Selection::First.reason(), "first handler");
+}
+
+#[test]
+fn test_forget() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = ChoiceStore::open(dir.path().join("choices.toml")).unwrap();
+    let key = ChoiceKey::Host("github.com".into());
+    store.remember(key.clone(), choice("firefox", false));
+
+    assert_eq!(store.forget(&key), Some(choice("firefox", false)));
+    assert_eq!(store.forget(&key), None);
+    assert!(store.get(&key).is_none());
+}
+
+#[test]
+fn test_open_invalid_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("choices.toml");
+    std::fs::write(&path, "[hosts]\n\"github.com\" = 42\n").unwrap();
+
+    let err = ChoiceStore::open(&path).unwrap_err();
+    assert!(matches!(err, Error::LoadChoices { .. }), "{:?}", err);
+}
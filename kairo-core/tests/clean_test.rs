@@ -0,0 +1,83 @@
+mod utils;
+
+use kairo_core::{Error, Url, UrlCleaner};
+
+fn clean(cleaner: &UrlCleaner, url: &str) -> (String, Vec<String>) {
+    let url: Url = url.parse().unwrap();
+    let cleaned = cleaner.clean(&url);
+    (cleaned.url.to_string(), cleaned.removed)
+}
+
+#[test]
+fn test_clean_global_params() {
+    let cleaner = UrlCleaner::builtin();
+
+    let (url, removed) = clean(
+        &cleaner,
+        "https://example.org/article?id=42&utm_source=newsletter&q=a%20b&fbclid=abc",
+    );
+    assert_eq!(url, "https://example.org/article?id=42&q=a%20b");
+    assert_eq!(removed, vec!["utm_source=newsletter", "fbclid=abc"]);
+}
+
+#[test]
+fn test_clean_removes_empty_query() {
+    let cleaner = UrlCleaner::builtin();
+
+    let (url, removed) = clean(
+        &cleaner,
+        "https://example.org/article?utm_medium=email&utm_campaign=launch#intro",
+    );
+    assert_eq!(url, "https://example.org/article#intro");
+    assert_eq!(removed.len(), 2);
+}
+
+#[test]
+fn test_clean_untouched_url() {
+    let cleaner = UrlCleaner::builtin();
+    let url: Url = "https://example.org/search?q=rust".parse().unwrap();
+
+    let cleaned = cleaner.clean(&url);
+    assert!(!cleaned.is_modified());
+    assert_eq!(cleaned.url, url);
+}
+
+#[test]
+fn test_clean_raw_rules() {
+    let cleaner = UrlCleaner::builtin();
+
+    let (url, removed) = clean(
+        &cleaner,
+        "https://www.amazon.com/dp/B000000000/ref=sr_1_1?keywords=book&qid=123",
+    );
+    assert_eq!(url, "https://www.amazon.com/dp/B000000000?keywords=book");
+    assert_eq!(removed, vec!["/ref=sr_1_1", "qid=123"]);
+}
+
+#[test]
+fn test_with_rules_file() {
+    let cleaner = UrlCleaner::builtin()
+        .with_rules_file(&utils::config_path().join("clearurls.json"))
+        .unwrap();
+
+    let (url, removed) = clean(
+        &cleaner,
+        "https://shop.example.com/item?id=1&session_token=x&ref=home&utm_source=ad",
+    );
+    assert_eq!(url, "https://shop.example.com/item?id=1");
+    assert_eq!(
+        removed,
+        vec!["utm_source=ad", "session_token=x", "ref=home"]
+    );
+
+    // Exceptions leave the URL untouched by the provider
+    let (url, _) = clean(&cleaner, "https://example.com/keep?ref=home");
+    assert_eq!(url, "https://example.com/keep?ref=home");
+}
+
+#[test]
+fn test_with_invalid_rules_file() {
+    let result = UrlCleaner::builtin().with_rules_file(&utils::config_path().join("rules.toml"));
+
+    assert!(matches!(result, Err(Error::LoadCleaningRules { .. })));
+}
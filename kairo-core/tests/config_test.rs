@@ -0,0 +1,342 @@
+mod utils;
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use kairo_core::{
+    AppConfig,
+    AppSettings,
+    Config,
+    CustomHandler,
+    DesktopEntries,
+    Error,
+    HandlerRegistry,
+    LaunchOptions,
+    LaunchWrapper,
+    MimeApps,
+    PickerConfig,
+    Url,
+};
+
+#[test]
+fn test_load_from() {
+    let path = utils::config_path().join("config.toml");
+    let config = Config::load_from(Some(&path)).unwrap();
+
+    assert_eq!(
+        config,
+        Config {
+            search_paths: Some(vec![PathBuf::from("/usr/share/applications")]),
+            locales: Some(vec!["de".into()]),
+            terminal: Some("kitty --single-instance {command}".into()),
+            theme: Some("Dracula".into()),
+            wrapper: LaunchWrapper::preset("systemd-run"),
+            picker: PickerConfig {
+                no_prompt: true,
+                show_actions: false,
+            },
+            handlers: vec![
+                CustomHandler {
+                    id: "firefox-work".into(),
+                    name: "Firefox (work)".into(),
+                    comment: None,
+                    icon: Some("firefox".into()),
+                    command: "firefox -P work --new-tab {urls}".into(),
+                    schemes: vec!["http".into(), "https".into()],
+                    terminal: false,
+                    env: BTreeMap::from([("MOZ_ENABLE_WAYLAND".into(), "1".into())]),
+                    working_dir: None,
+                },
+                CustomHandler {
+                    id: "mpv".into(),
+                    name: "mpv".into(),
+                    comment: None,
+                    icon: None,
+                    command: "mpv --ytdl {url}".into(),
+                    schemes: vec!["https".into(), "ytdl".into()],
+                    terminal: true,
+                    env: BTreeMap::new(),
+                    working_dir: None,
+                },
+            ],
+            apps: BTreeMap::from([(
+                "brave-browser".into(),
+                AppConfig {
+                    env: BTreeMap::from([("GTK_THEME".into(), "Adwaita:dark".into())]),
+                    working_dir: Some(PathBuf::from("/tmp")),
+                },
+            )]),
+            path: Some(path),
+            dir: Some(utils::config_path()),
+        }
+    );
+
+    let err = Config::load_from(Some(Path::new("/nonexistent/config.toml"))).unwrap_err();
+    assert!(matches!(err, Error::Io(_)));
+}
+
+#[test]
+fn test_load_from_dir() {
+    // The other files are read from the directory of the given configuration file
+    let config = Config::load_from(Some(&utils::config_path().join("config.toml"))).unwrap();
+    assert_eq!(config.router().unwrap().rules().len(), 5);
+    let url: Url = "https://shop.example.com/item?id=1&session_token=x"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        config.cleaner().unwrap().clean(&url).url.as_str(),
+        "https://shop.example.com/item?id=1"
+    );
+    assert_eq!(
+        config.choices().unwrap().path(),
+        utils::config_path().join("choices.toml")
+    );
+
+    // Missing files fall back to no rules and no remembered choices
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, "").unwrap();
+    let config = Config::load_from(Some(&path)).unwrap();
+    assert!(config.router().unwrap().rules().is_empty());
+    let store = config.choices().unwrap();
+    assert_eq!(store.path(), dir.path().join("choices.toml"));
+    assert_eq!(store.iter().count(), 0);
+}
+
+#[test]
+fn test_parse() {
+    let path = Path::new("config.toml");
+
+    let config = Config::parse("", path).unwrap();
+    assert_eq!(
+        config,
+        Config {
+            path: Some(path.to_path_buf()),
+            ..Default::default()
+        }
+    );
+    assert!(config.picker.show_actions);
+
+    let config = Config::parse("search_paths = [\"~/apps\", \"/apps\"]", path).unwrap();
+    let home = PathBuf::from(std::env::var_os("HOME").unwrap());
+    assert_eq!(
+        config.search_paths,
+        Some(vec![home.join("apps"), PathBuf::from("/apps")])
+    );
+
+    // Relative paths are resolved against the directory of the file
+    let config = Config::parse(
+        "search_paths = [\"apps\"]\n[apps.firefox]\nworking_dir = \"downloads\"\n",
+        Path::new("/etc/kairo/config.toml"),
+    )
+    .unwrap();
+    assert_eq!(
+        config.search_paths,
+        Some(vec![PathBuf::from("/etc/kairo/apps")])
+    );
+    assert_eq!(
+        config.apps["firefox"].working_dir,
+        Some(PathBuf::from("/etc/kairo/downloads"))
+    );
+
+    let config = Config::parse("[apps.firefox]\nworking_dir = \"~/Downloads\"\n", path).unwrap();
+    assert_eq!(
+        config.apps["firefox"].working_dir,
+        Some(home.join("Downloads"))
+    );
+
+    let config = Config::parse("[picker]\nno_prompt = true\n", path).unwrap();
+    assert!(config.picker.no_prompt);
+    assert!(config.picker.show_actions);
+
+    // Errors point at the offending line and column
+    let err = Config::parse("locales = [\"de\"]\nsearch_path = []\n", path).unwrap_err();
+    assert!(matches!(err, Error::LoadConfig { .. }));
+    let message = err.to_string();
+    assert!(message.contains("config.toml"), "{message}");
+    assert!(message.contains("line 2, column 1"), "{message}");
+    assert!(message.contains("unknown field `search_path`"), "{message}");
+
+    let config = Config::parse("wrapper = \"nice {command}\"", path).unwrap();
+    assert_eq!(
+        config.wrapper,
+        LaunchWrapper::from_template("nice {command}").ok()
+    );
+    let err = Config::parse("wrapper = \"nice 'unmatched\"", path).unwrap_err();
+    assert!(
+        err.to_string().contains("invalid launch wrapper template"),
+        "{err}"
+    );
+
+    let err = Config::parse("[picker]\nno_prompt = \"yes\"\n", path).unwrap_err();
+    assert!(err.to_string().contains("line 2, column 13"), "{err}");
+}
+
+#[test]
+fn test_custom_handlers() {
+    let config = Config::load_from(Some(&utils::config_path().join("config.toml"))).unwrap();
+
+    assert_eq!(config.handlers[0].exec(), "firefox -P work --new-tab %U");
+    assert_eq!(config.handlers[1].exec(), "mpv --ytdl %u");
+
+    let apps = config.custom_handlers("HTTPS");
+    assert_eq!(apps.len(), 2);
+    assert_eq!(apps[0].appid, "firefox-work");
+    assert_eq!(apps[0].name, "Firefox (work)");
+    assert!(!apps[0].terminal);
+    assert!(apps[1].terminal);
+    assert!(config.custom_handlers("mailto").is_empty());
+
+    // Custom handlers are listed after the discovered apps, replacing the ones with the same ID
+    let entries = DesktopEntries {
+        locales: None,
+        search_paths: Some(vec![utils::entries_path()]),
+        mime_apps: Some(MimeApps::default()),
+        visibility: Some(utils::visibility()),
+        index_path: None,
+    };
+    let registry = |config: &Config| {
+        HandlerRegistry::new()
+            .with_source(entries.clone())
+            .with_source(config.clone())
+            .with_source(AppSettings {
+                apps: config.apps.clone(),
+            })
+    };
+    let config = Config {
+        handlers: vec![CustomHandler {
+            id: "firefox".into(),
+            name: "Custom Firefox".into(),
+            comment: None,
+            icon: None,
+            command: "firefox --new-tab {url}".into(),
+            schemes: vec!["http".into(), "ipfs".into()],
+            terminal: false,
+            env: BTreeMap::new(),
+            working_dir: None,
+        }],
+        ..config
+    };
+    let apps = registry(&config).handlers_for_scheme("http").unwrap();
+    assert_eq!(apps.len(), 3);
+    assert_eq!(apps.iter().filter(|app| app.appid == "firefox").count(), 1);
+    assert!(apps.iter().any(|app| app.name == "Custom Firefox"));
+
+    // The launch settings apply to the discovered apps with the same ID
+    let brave = apps
+        .iter()
+        .find(|app| app.appid == "brave-browser")
+        .unwrap();
+    assert_eq!(brave.env["GTK_THEME"], "Adwaita:dark");
+    assert_eq!(brave.working_dir, Some(PathBuf::from("/tmp")));
+
+    // Custom handlers are enough for schemes without desktop entries
+    let config = Config {
+        handlers: vec![CustomHandler {
+            schemes: vec!["gemini".into()],
+            ..config.handlers[0].clone()
+        }],
+        ..config
+    };
+    let apps = registry(&config).handlers_for_scheme("gemini").unwrap();
+    assert_eq!(apps.len(), 1);
+    assert!(matches!(
+        registry(&config).handlers_for_scheme("ftp"),
+        Err(Error::NoHandlersFound(_))
+    ));
+}
+
+#[test]
+fn test_open_custom_handler() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let handler = CustomHandler {
+        id: "echo".into(),
+        name: "Echo".into(),
+        comment: None,
+        icon: None,
+        command: format!("sh -c 'echo \"$0\" > {}' {{url}}", out.display()),
+        schemes: vec!["https".into()],
+        terminal: false,
+        env: BTreeMap::new(),
+        working_dir: None,
+    };
+    let app = handler.to_app(Path::new("config.toml"));
+    let options = LaunchOptions {
+        detach: false,
+        ..Default::default()
+    };
+
+    app.open_url_with_options("https://github.com".parse().unwrap(), None, &options)
+        .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&out).unwrap(),
+        "https://github.com/\n"
+    );
+}
+
+#[test]
+fn test_custom_handler_commands() {
+    let handler = CustomHandler {
+        id: "yt-dlp".into(),
+        name: "Download\nExec=evil %u".into(),
+        comment: None,
+        icon: None,
+        command: "yt-dlp -o '%(title)s.%(ext)s' --exec 'echo \\\\' {urls}\nTerminal=true".into(),
+        schemes: vec!["https".into()],
+        terminal: false,
+        env: BTreeMap::new(),
+        working_dir: None,
+    };
+    assert_eq!(
+        handler.exec(),
+        "yt-dlp -o '%%(title)s.%%(ext)s' --exec 'echo \\\\' %U\nTerminal=true"
+    );
+
+    // The command is expanded as is, whatever the name and command hold
+    let app = handler.to_app(Path::new("config.toml"));
+    let urls = [
+        "https://example.com/a".parse().unwrap(),
+        "https://example.com/b".parse().unwrap(),
+    ];
+    assert_eq!(
+        app.commands(&urls, None, &LaunchOptions::default())
+            .unwrap(),
+        vec![vec![
+            "yt-dlp",
+            "-o",
+            "%(title)s.%(ext)s",
+            "--exec",
+            "echo \\\\",
+            "https://example.com/a",
+            "https://example.com/b",
+            "Terminal=true",
+        ]]
+    );
+    assert!(!app.uses_dbus(None, &LaunchOptions::default()).unwrap());
+}
+
+#[test]
+fn test_invalid_custom_handlers() {
+    let path = Path::new("config.toml");
+    let handler = |keys: &str| format!("[[handlers]]\nid = \"test\"\nname = \"Test\"\n{keys}\n");
+
+    let err = Config::parse(&handler("command = \"app {url}\""), path).unwrap_err();
+    assert!(err.to_string().contains("missing field `schemes`"), "{err}");
+
+    let err = Config::parse(&handler("command = \"app {url}\"\nschemes = []"), path).unwrap_err();
+    assert!(matches!(err, Error::InvalidCustomHandler { id, .. } if id == "test"));
+
+    let err =
+        Config::parse(&handler("command = \"app\"\nschemes = [\"https\"]"), path).unwrap_err();
+    assert!(err.to_string().contains("no {url} or {urls}"), "{err}");
+
+    let err = Config::parse(
+        &handler("command = \"app '{url}\"\nschemes = [\"https\"]"),
+        path,
+    )
+    .unwrap_err();
+    assert!(matches!(err, Error::InvalidCustomHandler { .. }));
+}
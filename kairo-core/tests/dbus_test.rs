@@ -0,0 +1,287 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+};
+
+use freedesktop_desktop_entry as fde;
+use kairo_core::{
+    ActivationToken,
+    DBusActivator,
+    Error,
+    LaunchOptions,
+    LaunchWrapper,
+    UrlHandlerApp,
+    dbus,
+    exec,
+};
+use zbus::zvariant::OwnedValue;
+
+/// A private session bus, stopped when dropped.
+struct Bus {
+    daemon: Child,
+    address: String,
+}
+
+impl Bus {
+    fn start() -> Option<Self> {
+        let Some(program) = exec::find_program("dbus-daemon") else {
+            eprintln!("dbus-daemon not found, skipping test");
+            return None;
+        };
+
+        let mut daemon = Command::new(program)
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+
+        Some(Self {
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// Values received by a stub application.
+type Received = Arc<Mutex<Vec<String>>>;
+
+/// Records the URIs and activation tokens received by a stub application.
+struct StubApplication {
+    opened: Received,
+    tokens: Received,
+}
+
+#[zbus::interface(name = "org.freedesktop.Application")]
+impl StubApplication {
+    fn open(&self, uris: Vec<String>, platform_data: HashMap<String, OwnedValue>) {
+        self.opened.lock().unwrap().extend(uris);
+        for key in ["activation-token", "desktop-startup-id"] {
+            if let Some(token) = platform_data.get(key) {
+                let token = String::try_from(token.try_clone().unwrap()).unwrap();
+                self.tokens.lock().unwrap().push(format!("{key}={token}"));
+            }
+        }
+    }
+}
+
+impl Bus {
+    /// Serves a stub application under the given app ID, returning the URIs and tokens it
+    /// receives. The application is served as long as the connection is kept.
+    fn serve(&self, appid: &str) -> (zbus::blocking::Connection, Received, Received) {
+        let opened = Arc::new(Mutex::new(vec![]));
+        let tokens = Arc::new(Mutex::new(vec![]));
+        let stub = StubApplication {
+            opened: opened.clone(),
+            tokens: tokens.clone(),
+        };
+        let connection = zbus::blocking::connection::Builder::address(self.address.as_str())
+            .unwrap()
+            .name(appid)
+            .unwrap()
+            .serve_at(dbus::object_path(appid), stub)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        (connection, opened, tokens)
+    }
+
+    /// Launch options activating apps on this bus, waiting for the fallback commands to exit.
+    fn options(&self) -> LaunchOptions {
+        LaunchOptions {
+            detach: false,
+            dbus: Some(DBusActivator::with_address(&self.address).unwrap()),
+            ..Default::default()
+        }
+    }
+}
+
+/// A D-Bus activatable app whose Exec key exits with code 4.
+fn dbus_app(dir: &std::path::Path, appid: &str) -> UrlHandlerApp {
+    let path = dir.join(format!("{appid}.desktop"));
+    std::fs::write(
+        &path,
+        "[Desktop Entry]\nType=Application\nName=Test\nDBusActivatable=true\nExec=sh -c 'exit 4' %u\n",
+    )
+    .unwrap();
+
+    let locales: [String; 0] = [];
+    let de = fde::DesktopEntry::from_path(path, Some(&locales)).unwrap();
+    UrlHandlerApp::from_desktop_entry(de, &locales)
+}
+
+#[test]
+fn test_object_path() {
+    assert_eq!(
+        dbus::object_path("org.gnome.Epiphany"),
+        "/org/gnome/Epiphany"
+    );
+    assert_eq!(
+        dbus::object_path("org.example.web-browser"),
+        "/org/example/web_browser"
+    );
+}
+
+#[test]
+fn test_open() {
+    let Some(bus) = Bus::start() else {
+        return;
+    };
+
+    let (_service, opened, tokens) = bus.serve("org.example.Browser");
+
+    let activator = DBusActivator::with_address(&bus.address).unwrap();
+    activator
+        .open(
+            "org.example.Browser",
+            &["https://example.com/", "https://example.org/"],
+            None,
+        )
+        .unwrap();
+    assert_eq!(
+        *opened.lock().unwrap(),
+        ["https://example.com/", "https://example.org/"]
+    );
+    assert!(tokens.lock().unwrap().is_empty());
+
+    // The activation token is passed in the platform data
+    let token = ActivationToken::wayland("kairo-token");
+    activator
+        .open(
+            "org.example.Browser",
+            &["https://example.net/"],
+            Some(&token),
+        )
+        .unwrap();
+    assert_eq!(*tokens.lock().unwrap(), ["activation-token=kairo-token"]);
+
+    // Under the key of the windowing system the token is for
+    tokens.lock().unwrap().clear();
+    let token = ActivationToken::x11("kairo-token");
+    activator
+        .open(
+            "org.example.Browser",
+            &["https://example.net/"],
+            Some(&token),
+        )
+        .unwrap();
+    assert_eq!(*tokens.lock().unwrap(), ["desktop-startup-id=kairo-token"]);
+
+    let err = activator
+        .open("org.example.Missing", &["https://example.com/"], None)
+        .unwrap_err();
+    assert!(matches!(err, Error::DBus(_)), "{err:?}");
+}
+
+#[test]
+fn test_open_url_activates_over_dbus() {
+    let Some(bus) = Bus::start() else {
+        return;
+    };
+    let (_service, opened, tokens) = bus.serve("org.example.Browser");
+    let dir = tempfile::tempdir().unwrap();
+    let app = dbus_app(dir.path(), "org.example.Browser");
+
+    let options = LaunchOptions {
+        activation_token: Some(ActivationToken::wayland("kairo-token")),
+        ..bus.options()
+    };
+    assert!(app.uses_dbus(None, &options).unwrap());
+    app.open_url_with_options("https://example.com".parse().unwrap(), None, &options)
+        .unwrap();
+    assert_eq!(*opened.lock().unwrap(), ["https://example.com/"]);
+    assert_eq!(*tokens.lock().unwrap(), ["activation-token=kairo-token"]);
+}
+
+#[test]
+fn test_open_url_falls_back_to_exec() {
+    let Some(bus) = Bus::start() else {
+        return;
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let app = dbus_app(dir.path(), "org.example.Missing");
+
+    let options = bus.options();
+    assert!(app.uses_dbus(None, &options).unwrap());
+    assert!(!app.uses_dbus(Some("new-window"), &options).unwrap());
+
+    // The service doesn't exist on the bus, so the Exec key is used
+    let result = app.open_url_with_options("https://example.com".parse().unwrap(), None, &options);
+    assert!(matches!(
+        result,
+        Err(Error::OpenUrl(_, status)) if status.code() == Some(4)
+    ));
+}
+
+#[test]
+fn test_open_url_with_wrapper_uses_exec() {
+    let Some(bus) = Bus::start() else {
+        return;
+    };
+    let (_service, opened, _) = bus.serve("org.example.Wrapped");
+    let dir = tempfile::tempdir().unwrap();
+    let app = dbus_app(dir.path(), "org.example.Wrapped");
+    let wrapper = LaunchWrapper::from_template("sh -c 'exit 6' {command}").unwrap();
+
+    // D-Bus activation would bypass the wrapper, so the wrapped Exec key is run instead
+    let options = LaunchOptions {
+        wrapper: Some(wrapper.clone()),
+        ..bus.options()
+    };
+    assert!(!app.uses_dbus(None, &options).unwrap());
+    let result = app.open_url_with_options("https://example.com".parse().unwrap(), None, &options);
+    assert!(matches!(
+        result,
+        Err(Error::OpenUrl(_, status)) if status.code() == Some(6)
+    ));
+    assert!(opened.lock().unwrap().is_empty());
+
+    // Same with the wrapper of the app
+    let app = UrlHandlerApp {
+        wrapper: Some(wrapper),
+        ..app
+    };
+    assert!(!app.uses_dbus(None, &LaunchOptions::default()).unwrap());
+}
+
+#[test]
+fn test_open_url_with_working_dir_uses_exec() {
+    let Some(bus) = Bus::start() else {
+        return;
+    };
+    let (_service, opened, _) = bus.serve("org.example.Workdir");
+    let dir = tempfile::tempdir().unwrap();
+    let app = dbus_app(dir.path(), "org.example.Workdir");
+
+    // The activated service wouldn't run in the working directory, so the Exec key is run instead
+    let options = LaunchOptions {
+        working_dir: Some(dir.path().to_path_buf()),
+        ..bus.options()
+    };
+    assert!(!app.uses_dbus(None, &options).unwrap());
+    let result = app.open_url_with_options("https://example.com".parse().unwrap(), None, &options);
+    assert!(matches!(
+        result,
+        Err(Error::OpenUrl(_, status)) if status.code() == Some(4)
+    ));
+    assert!(opened.lock().unwrap().is_empty());
+
+    // Same with the working directory of the app, such as the Path key of its desktop entry
+    let app = app.with_env(&Default::default(), Some(dir.path()));
+    assert!(!app.uses_dbus(None, &LaunchOptions::default()).unwrap());
+}
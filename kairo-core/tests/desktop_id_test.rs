@@ -0,0 +1,60 @@
+mod utils;
+
+use std::path::Path;
+
+use kairo_core::{HandlerIndex, MimeApps, UrlHandlerApp, desktop_id};
+
+#[test]
+fn test_desktop_id() {
+    let root = Path::new("/usr/share/applications");
+
+    assert_eq!(
+        desktop_id::desktop_id(root, &root.join("firefox.desktop")),
+        Some("firefox".into())
+    );
+    assert_eq!(
+        desktop_id::desktop_id(root, &root.join("kde/konqueror.desktop")),
+        Some("kde-konqueror".into())
+    );
+    assert_eq!(
+        desktop_id::desktop_id(root, &root.join("org.gnome.Epiphany.desktop")),
+        Some("org.gnome.Epiphany".into())
+    );
+    assert_eq!(
+        desktop_id::desktop_id(root, Path::new("/opt/app.desktop")),
+        None
+    );
+    assert_eq!(desktop_id::desktop_id(root, &root.join("README")), None);
+}
+
+#[test]
+fn test_desktop_id_precedence() {
+    let path = utils::desktop_ids_path();
+    let search_paths = vec![
+        path.join("user/applications"),
+        path.join("system/applications"),
+    ];
+
+    // The user's entries override the system ones, and hidden ones mask them
+    let scan = UrlHandlerApp::scan_scheme_with_index(
+        "http",
+        None,
+        Some(search_paths),
+        &MimeApps::default(),
+        &utils::visibility(),
+        &mut HandlerIndex::in_memory(),
+    )
+    .unwrap();
+
+    let apps = scan
+        .apps
+        .iter()
+        .map(|app| (app.appid.as_str(), app.name.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        apps,
+        vec![("browser", "User Browser"), ("kde-konqueror", "Konqueror"),]
+    );
+    // Masking entries don't handle the scheme, so they aren't reported as excluded
+    assert!(scan.excluded.is_empty());
+}
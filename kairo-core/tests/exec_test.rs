@@ -1,6 +1,7 @@
+use freedesktop_desktop_entry as fde;
 use kairo_core::{
     Error,
-    exec::{ExecParseError, ExecParser},
+    exec::{self, ExecParseError, ExecParser, ExecTargets},
 };
 
 mod utils;
@@ -81,3 +82,288 @@ fn test_parse_with_uris_invalid_exec_args() {
         err
     );
 }
+
+#[test]
+fn test_parse_action_with_uris() {
+    let locales: [String; 0] = [];
+    let de = utils::black_hole_de(Some(&locales));
+
+    let url: url::Url = "http://example.com".parse().unwrap();
+
+    let (cmd, args) = ExecParser::new(&de, &locales)
+        .parse_action_with_uris("new-private-window", &[url.as_str()])
+        .unwrap();
+
+    assert_eq!(cmd, "/dev/null");
+    assert_eq!(
+        args,
+        vec![
+            "--private-window",
+            "--arg",
+            "Value with spaces",
+            "http://example.com/"
+        ]
+    );
+
+    let err = ExecParser::new(&de, &locales)
+        .parse_action_with_uris("missing", &[url.as_str()])
+        .unwrap_err();
+
+    assert!(
+        matches!(
+            err,
+            Error::ParseExecArgs(ExecParseError::ActionExecFieldNotFound { ref action, .. }) if action == "missing"
+        ),
+        "{:?}",
+        err
+    );
+}
+
+fn parse_exec_line(exec: &str, uris: &[&str]) -> kairo_core::Result<Vec<String>> {
+    let locales: [String; 0] = [];
+    let entry =
+        format!("[Desktop Entry]\nType=Application\nName=Browser\nIcon=browser\nExec={exec}\n");
+    let de = fde::DesktopEntry::from_str(
+        "/usr/share/applications/browser.desktop",
+        &entry,
+        Some(&locales),
+    )
+    .unwrap();
+
+    let (cmd, args) = ExecParser::new(&de, &locales).parse_with_uris(uris)?;
+    Ok([vec![cmd], args].concat())
+}
+
+#[test]
+fn test_parse_real_world_exec_lines() {
+    let url = "https://example.com/?q=a%20b";
+    let cases: &[(&str, &[&str])] = &[
+        (
+            "/usr/lib/firefox/firefox %u",
+            &["/usr/lib/firefox/firefox", url],
+        ),
+        (
+            "/usr/bin/google-chrome-stable %U",
+            &["/usr/bin/google-chrome-stable", url],
+        ),
+        (
+            "/usr/bin/brave-browser-stable --incognito",
+            &["/usr/bin/brave-browser-stable", "--incognito"],
+        ),
+        (
+            "/usr/bin/flatpak run --branch=stable --arch=x86_64 --command=firefox --file-forwarding org.mozilla.firefox @@u %u @@",
+            &[
+                "/usr/bin/flatpak",
+                "run",
+                "--branch=stable",
+                "--arch=x86_64",
+                "--command=firefox",
+                "--file-forwarding",
+                "org.mozilla.firefox",
+                "@@u",
+                url,
+                "@@",
+            ],
+        ),
+        (
+            "env BAMF_DESKTOP_FILE_HINT=/var/lib/snapd/desktop/applications/chromium_chromium.desktop /snap/bin/chromium %U",
+            &[
+                "env",
+                "BAMF_DESKTOP_FILE_HINT=/var/lib/snapd/desktop/applications/chromium_chromium.desktop",
+                "/snap/bin/chromium",
+                url,
+            ],
+        ),
+        (
+            r#""/opt/Tor Browser/start-tor-browser" --detach %u"#,
+            &["/opt/Tor Browser/start-tor-browser", "--detach", url],
+        ),
+        (
+            r#"sh -c '"/opt/tor-browser/Browser/start-tor-browser" --detach' dummy %k"#,
+            &[
+                "sh",
+                "-c",
+                r#""/opt/tor-browser/Browser/start-tor-browser" --detach"#,
+                "dummy",
+                "/usr/share/applications/browser.desktop",
+            ],
+        ),
+        // String escapes are decoded before the Exec quoting rules apply
+        (
+            r#"sh -c "exec firefox --new-tab \\"\\$0\\"" %u"#,
+            &["sh", "-c", r#"exec firefox --new-tab "$0""#, url],
+        ),
+        (
+            r"app --name=My\sBrowser %u",
+            &["app", "--name=My", "Browser", url],
+        ),
+        // Field codes inside arguments and literal percent signs
+        ("app --url=%u", &["app", &format!("--url={url}")]),
+        (
+            "app --format=%%s --zoom=100%% %U",
+            &["app", "--format=%s", "--zoom=100%", url],
+        ),
+        (
+            "app %i --class=%c %u",
+            &["app", "--icon", "browser", "--class=Browser", url],
+        ),
+        // Deprecated and unknown field codes are removed
+        ("app %d %x --flag%m %u", &["app", "--flag", url]),
+        (r#"app "" %u"#, &["app", "", url]),
+    ];
+
+    for (exec, expected) in cases {
+        assert_eq!(
+            &parse_exec_line(exec, &[url]).unwrap(),
+            expected,
+            "Exec={exec}"
+        );
+    }
+}
+
+#[test]
+fn test_parse_exec_multiple_uris() {
+    let uris = ["https://a.com/", "https://b.com/"];
+
+    assert_eq!(
+        parse_exec_line("app %U", &uris).unwrap(),
+        ["app", "https://a.com/", "https://b.com/"]
+    );
+    assert_eq!(
+        parse_exec_line("app %u", &uris).unwrap(),
+        ["app", "https://a.com/"]
+    );
+    assert_eq!(parse_exec_line("app %u", &[]).unwrap(), ["app"]);
+}
+
+#[test]
+fn test_parse_exec_file_field_codes() {
+    let uris = [
+        "file:///home/user/My%20Documents/report.pdf",
+        "https://example.com/remote.pdf",
+        "file:///tmp/notes.txt",
+    ];
+
+    // Local paths are percent-decoded and remote URLs skipped
+    assert_eq!(
+        parse_exec_line("viewer %F", &uris).unwrap(),
+        [
+            "viewer",
+            "/home/user/My Documents/report.pdf",
+            "/tmp/notes.txt"
+        ]
+    );
+    assert_eq!(
+        parse_exec_line("viewer --file=%f", &uris).unwrap(),
+        ["viewer", "--file=/home/user/My Documents/report.pdf"]
+    );
+    assert_eq!(
+        parse_exec_line("viewer %f", &["https://example.com/remote.pdf"]).unwrap(),
+        ["viewer"]
+    );
+    // URL field codes still receive the URLs
+    assert_eq!(
+        parse_exec_line("browser %u", &uris).unwrap(),
+        ["browser", "file:///home/user/My%20Documents/report.pdf"]
+    );
+}
+
+#[test]
+fn test_exec_targets() {
+    assert_eq!(exec::targets("firefox %u"), ExecTargets::Urls);
+    assert_eq!(exec::targets("app %F --url=%U"), ExecTargets::Urls);
+    assert_eq!(exec::targets("viewer --file=%f"), ExecTargets::Files);
+    assert_eq!(exec::targets("brave --incognito"), ExecTargets::None);
+}
+
+#[test]
+fn test_parse_exec_unmatched_quotes() {
+    for exec in [r#"app "%u"#, "app '%u", r#"app "\\"#] {
+        let err = parse_exec_line(exec, &[]).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::ParseExecArgs(ExecParseError::InvalidFormat { .. })
+            ),
+            "Exec={exec}: {err:?}"
+        );
+    }
+}
+
+#[test]
+fn test_accepts_uris() {
+    assert!(exec::accepts_uris("firefox %u"));
+    assert!(exec::accepts_uris("app --url=%U"));
+    assert!(!exec::accepts_uris("brave --incognito"));
+    assert!(!exec::accepts_uris("app --format=%%u"));
+    assert!(!exec::accepts_uris("app \"%u"));
+}
+
+#[test]
+fn test_accepts_multiple() {
+    assert!(exec::accepts_multiple("firefox %U"));
+    assert!(exec::accepts_multiple("viewer %F"));
+    assert!(!exec::accepts_multiple("firefox %u"));
+    assert!(!exec::accepts_multiple("app --url=%U"));
+    assert!(!exec::accepts_multiple("app %%U"));
+}
+
+#[test]
+fn test_insert_args() {
+    let insert = |exec: &str| {
+        let mut args = exec.split(' ').map(str::to_string).collect::<Vec<_>>();
+        exec::insert_args(&mut args, &["-P".to_string(), "100%".to_string()]);
+        args.join(" ")
+    };
+
+    assert_eq!(insert("firefox %u"), "firefox -P 100%% %u");
+    assert_eq!(
+        insert("firefox --new-window --url=%u"),
+        "firefox --new-window -P 100%% --url=%u"
+    );
+    assert_eq!(
+        insert(
+            "env BAMF_DESKTOP_FILE_HINT=/var/lib/snapd/desktop/applications/chromium_chromium.desktop /snap/bin/chromium %U"
+        ),
+        "env BAMF_DESKTOP_FILE_HINT=/var/lib/snapd/desktop/applications/chromium_chromium.desktop /snap/bin/chromium -P 100%% %U"
+    );
+    assert_eq!(
+        insert("/usr/bin/flatpak run --file-forwarding org.mozilla.firefox @@u %u @@"),
+        "/usr/bin/flatpak run --file-forwarding org.mozilla.firefox -P 100%% @@u %u @@"
+    );
+    assert_eq!(insert("firefox"), "firefox -P 100%%");
+}
+
+#[test]
+fn test_wrap_command() {
+    let wrap = |template: &str| {
+        let template = template.split(' ').map(str::to_string).collect::<Vec<_>>();
+        let (cmd, args) = exec::wrap_command(
+            &template,
+            "w3m".into(),
+            vec!["https://example.com/".into()],
+            |arg| arg.replace("{name}", "w3m"),
+        );
+        [vec![cmd], args].concat()
+    };
+
+    assert_eq!(
+        wrap("kitty --title={name} {command} --hold"),
+        [
+            "kitty",
+            "--title=w3m",
+            "w3m",
+            "https://example.com/",
+            "--hold"
+        ]
+    );
+    assert_eq!(
+        wrap("foot --title={name}"),
+        ["foot", "--title=w3m", "w3m", "https://example.com/"]
+    );
+    // The placeholder only stands for the command as a whole argument
+    assert_eq!(
+        wrap("sh -c {command}x"),
+        ["sh", "-c", "{command}x", "w3m", "https://example.com/"]
+    );
+}
@@ -1,25 +1,46 @@
 mod utils;
 
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
 use freedesktop_desktop_entry as fde;
-use kairo_core::UrlHandlerApp;
+use kairo_core::{
+    ActivationToken,
+    AppAction,
+    LaunchOptions,
+    MimeApps,
+    ProgramRole,
+    UrlBatches,
+    UrlHandlerApp,
+};
 
 #[test]
 fn test_handlers_for_scheme() {
-    let search_paths = vec![utils::entries_path()];
-
-    let apps =
-        UrlHandlerApp::handlers_for_scheme("http", None, Some(search_paths.clone())).unwrap();
+    let apps = utils::handlers_for_scheme("http", &MimeApps::default()).unwrap();
     assert_eq!(apps.len(), 3);
 
-    let apps =
-        UrlHandlerApp::handlers_for_scheme("ipfs", None, Some(search_paths.clone())).unwrap();
+    let apps = utils::handlers_for_scheme("ipfs", &MimeApps::default()).unwrap();
     assert_eq!(apps.len(), 1);
 
-    let err =
-        UrlHandlerApp::handlers_for_scheme("file", None, Some(search_paths.clone())).unwrap_err();
+    let err = utils::handlers_for_scheme("file", &MimeApps::default()).unwrap_err();
     assert!(matches!(err, kairo_core::Error::NoHandlersFound(_)));
 }
 
+#[test]
+fn test_handlers_for_scheme_file_only() {
+    // File-only handlers can't open remote URLs
+    let err = utils::handlers_for_scheme("smb", &MimeApps::default()).unwrap_err();
+    assert!(matches!(err, kairo_core::Error::NoHandlersFound(_)));
+
+    let mime_apps = MimeApps::from_paths([utils::mimeapps_path().join("config/mimeapps.list")]);
+    let apps = utils::handlers_for_scheme("file", &mime_apps).unwrap();
+    assert_eq!(apps.len(), 1);
+    assert_eq!(apps[0].appid, "file-viewer");
+    assert_eq!(apps[0].actions.len(), 1);
+}
+
 #[test]
 fn test_from_desktop_entry() {
     let entries_path = utils::entries_path();
@@ -37,6 +58,20 @@ fn test_from_desktop_entry() {
         fde::IconSource::Name(name) if name == app.appid
     ));
     assert_eq!(app.path.to_path_buf(), de.path);
+    // Skips actions that can't receive the URL
+    assert_eq!(
+        app.actions,
+        vec![
+            AppAction {
+                id: "new-window".into(),
+                name: "New Window".into()
+            },
+            AppAction {
+                id: "new-private-window".into(),
+                name: "New Private Window".into()
+            },
+        ]
+    );
 
     // Loads localized name and comment
     let locales = ["de"];
@@ -49,6 +84,32 @@ fn test_from_desktop_entry() {
         "Ein minimalistischer Browser"
     );
     assert_eq!(app.icon, fde::IconSource::default());
+
+    let de =
+        fde::DesktopEntry::from_path(entries_path.join("brave-browser.desktop"), Some(&locales))
+            .unwrap();
+    let app = UrlHandlerApp::from_desktop_entry(de, &locales);
+    assert!(app.actions.is_empty());
+}
+
+#[test]
+fn test_display_name() {
+    let locales: [String; 0] = [];
+    let de = utils::black_hole_de(Some(&locales));
+    let app = UrlHandlerApp::from_desktop_entry(de, &locales);
+
+    let names = app
+        .launch_targets()
+        .map(|action| app.display_name(action))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec![
+            "Black Hole Browser",
+            "Black Hole Browser — New Window",
+            "Black Hole Browser — New Private Window",
+        ]
+    );
 }
 
 #[test]
@@ -60,6 +121,9 @@ fn test_open_url() {
     let result = app.open_url("https://github.com".parse().unwrap());
     assert!(result.is_ok());
 
+    let result = app.open_url_with_action("https://github.com".parse().unwrap(), Some("missing"));
+    assert!(matches!(result, Err(kairo_core::Error::ParseExecArgs(_))));
+
     let de = utils::fail_exec_de(Some(&locales));
     let app = UrlHandlerApp::from_desktop_entry(de, &locales);
 
@@ -71,3 +135,355 @@ fn test_open_url() {
         kairo_core::Error::OpenUrl(appid, status) if appid == app.appid && dbg!(status.code()).is_some_and(|code| code == 127)
     ));
 }
+
+fn app_with_exec(dir: &std::path::Path, exec: &str) -> UrlHandlerApp {
+    app_with_keys(dir, exec, "")
+}
+
+/// Writes a `launch-test` desktop entry with the given Exec value and extra keys in the directory.
+fn app_with_keys(dir: &std::path::Path, exec: &str, keys: &str) -> UrlHandlerApp {
+    let path = dir.join("launch-test.desktop");
+    std::fs::write(
+        &path,
+        format!("[Desktop Entry]\nType=Application\nName=Launch Test\nExec={exec}\n{keys}\n"),
+    )
+    .unwrap();
+
+    let locales: [String; 0] = [];
+    let de = fde::DesktopEntry::from_path(path, Some(&locales)).unwrap();
+    UrlHandlerApp::from_desktop_entry(de, &locales)
+}
+
+#[test]
+fn test_open_url_detached() {
+    let dir = tempfile::tempdir().unwrap();
+    let url: kairo_core::Url = "https://github.com".parse().unwrap();
+
+    // Returns while the application is still running
+    let app = app_with_exec(dir.path(), "sh -c 'sleep 5' %u");
+    let started = Instant::now();
+    let result = app.open_url(url.clone());
+    assert!(result.is_ok(), "{result:?}");
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    // Early failures are reported
+    let app = app_with_exec(dir.path(), "sh -c 'exit 2' %u");
+    let result = app.open_url(url.clone());
+    assert!(matches!(
+        result,
+        Err(kairo_core::Error::OpenUrl(_, status)) if status.code() == Some(2)
+    ));
+
+    // Failures after the grace period are not
+    let app = app_with_exec(dir.path(), "sh -c 'sleep 1; exit 2' %u");
+    let options = LaunchOptions {
+        grace_period: Duration::from_millis(50),
+        ..Default::default()
+    };
+    let result = app.open_url_with_options(url.clone(), None, &options);
+    assert!(result.is_ok(), "{result:?}");
+
+    let app = app_with_exec(dir.path(), "/nonexistent/browser %u");
+    assert!(app.open_url(url).is_err());
+}
+
+#[test]
+fn test_open_url_program_not_found() {
+    let dir = tempfile::tempdir().unwrap();
+    let url: kairo_core::Url = "https://github.com".parse().unwrap();
+
+    for exec in ["kairo-missing-browser %u", "/nonexistent/browser %u"] {
+        let app = app_with_exec(dir.path(), exec);
+        let err = app.open_url(url.clone()).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                kairo_core::Error::ProgramNotFound { appid, path, program, role }
+                    if appid == "launch-test"
+                        && path.ends_with("launch-test.desktop")
+                        && exec.starts_with(program.as_str())
+                        && *role == ProgramRole::App
+            ),
+            "{err:?}"
+        );
+        assert!(err.to_string().contains("launch-test.desktop"), "{err}");
+        assert_eq!(err.exit_code(), kairo_core::EXIT_PROGRAM_NOT_FOUND);
+    }
+
+    // A missing terminal emulator is reported too
+    let app = app_with_keys(dir.path(), "sh %u", "Terminal=true");
+    let options = LaunchOptions {
+        terminal: Some(kairo_core::Terminal::from_template("missing-terminal -e").unwrap()),
+        ..Default::default()
+    };
+    let err = app.open_url_with_options(url, None, &options).unwrap_err();
+    assert!(matches!(
+        &err,
+        kairo_core::Error::ProgramNotFound { program, role: ProgramRole::Terminal, .. }
+            if program == "missing-terminal"
+    ));
+    assert!(err.to_string().contains("--terminal"), "{err}");
+}
+
+#[test]
+fn test_open_url_wait() {
+    let dir = tempfile::tempdir().unwrap();
+    let url: kairo_core::Url = "https://github.com".parse().unwrap();
+    let options = LaunchOptions {
+        detach: false,
+        grace_period: Duration::ZERO,
+        ..Default::default()
+    };
+
+    let app = app_with_exec(dir.path(), "sh -c 'sleep 0.5; exit 2' %u");
+    let result = app.open_url_with_options(url, None, &options);
+    assert!(matches!(
+        result,
+        Err(kairo_core::Error::OpenUrl(_, status)) if status.code() == Some(2)
+    ));
+}
+
+#[test]
+fn test_open_urls() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let urls: Vec<kairo_core::Url> = vec![
+        "https://github.com".parse().unwrap(),
+        "https://gitlab.com".parse().unwrap(),
+    ];
+    let options = LaunchOptions {
+        detach: false,
+        ..Default::default()
+    };
+    let launches = || {
+        let launches = std::fs::read_to_string(&out).unwrap();
+        std::fs::remove_file(&out).unwrap();
+        launches.lines().map(str::to_string).collect::<Vec<_>>()
+    };
+
+    // %U handlers are launched once with all the URLs
+    let exec = format!("sh -c 'echo \"$*\" >> {}' sh %U", out.display());
+    let app = app_with_exec(dir.path(), &exec);
+    app.open_urls_with_options(&urls, None, &options).unwrap();
+    assert_eq!(launches(), vec!["https://github.com/ https://gitlab.com/"]);
+
+    // %u handlers are launched once per URL
+    let exec = format!("sh -c 'echo \"$*\" >> {}' sh %u", out.display());
+    let app = app_with_exec(dir.path(), &exec);
+    app.open_urls_with_options(&urls, None, &options).unwrap();
+    assert_eq!(
+        launches(),
+        vec!["https://github.com/", "https://gitlab.com/"]
+    );
+}
+
+#[test]
+fn test_url_batches() {
+    let locales: [String; 0] = [];
+    let black_hole =
+        UrlHandlerApp::from_desktop_entry(utils::black_hole_de(Some(&locales)), &locales);
+    let fail = UrlHandlerApp::from_desktop_entry(utils::fail_exec_de(Some(&locales)), &locales);
+    let new_window = black_hole.action("new-window").cloned();
+
+    let mut batches = UrlBatches::new();
+    assert!(batches.is_empty());
+    batches.push(&black_hole, None, "https://a.com".parse().unwrap());
+    batches.push(&fail, None, "https://b.com".parse().unwrap());
+    batches.push(
+        &black_hole,
+        new_window.as_ref(),
+        "https://c.com".parse().unwrap(),
+    );
+    batches.push(&black_hole, None, "https://d.com".parse().unwrap());
+
+    let batches = batches
+        .iter()
+        .map(|batch| {
+            (
+                batch.app.appid.as_str(),
+                batch.action.as_ref().map(|action| action.id.as_str()),
+                batch
+                    .urls
+                    .iter()
+                    .map(|url| url.host_str().unwrap())
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        batches,
+        vec![
+            ("black-hole", None, vec!["a.com", "d.com"]),
+            ("fail", None, vec!["b.com"]),
+            ("black-hole", Some("new-window"), vec!["c.com"]),
+        ]
+    );
+}
+
+#[test]
+fn test_open_url_env_and_working_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let workdir = tempfile::tempdir().unwrap();
+    let url: kairo_core::Url = "https://github.com".parse().unwrap();
+    let options = LaunchOptions {
+        detach: false,
+        ..Default::default()
+    };
+
+    // Runs in the directory from the Path key
+    let app = app_with_keys(
+        dir.path(),
+        "sh -c 'echo \"$KAIRO_TEST\" > out' %u",
+        &format!("Path={}", workdir.path().display()),
+    );
+    assert_eq!(app.working_dir.as_deref(), Some(workdir.path()));
+    app.open_url_with_options(url.clone(), None, &options)
+        .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(workdir.path().join("out")).unwrap(),
+        "\n"
+    );
+
+    // Environment variables and working directories are set on top of the entry's
+    let env = BTreeMap::from([("KAIRO_TEST".to_string(), "set".to_string())]);
+    let app = app.with_env(&env, Some(dir.path()));
+    app.open_url_with_options(url, None, &options).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("out")).unwrap(),
+        "set\n"
+    );
+
+    // Apps with a different environment are batched apart
+    let mut batches = UrlBatches::new();
+    batches.push(&app, None, "https://a.com".parse().unwrap());
+    batches.push(
+        &app.with_env(&BTreeMap::new(), None),
+        None,
+        "https://b.com".parse().unwrap(),
+    );
+    batches.push(
+        &app.with_env(&env, None),
+        None,
+        "https://c.com".parse().unwrap(),
+    );
+    let env = BTreeMap::from([("KAIRO_TEST".to_string(), "other".to_string())]);
+    batches.push(
+        &app.with_env(&env, None),
+        None,
+        "https://d.com".parse().unwrap(),
+    );
+    assert_eq!(
+        batches
+            .iter()
+            .map(|batch| batch.urls.len())
+            .collect::<Vec<_>>(),
+        vec![3, 1]
+    );
+}
+
+#[test]
+fn test_open_url_activation_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let url: kairo_core::Url = "https://github.com".parse().unwrap();
+    let options = LaunchOptions {
+        detach: false,
+        activation_token: Some(ActivationToken::wayland("kairo-token")),
+        ..Default::default()
+    };
+    let exec = format!(
+        "sh -c 'echo \"$XDG_ACTIVATION_TOKEN $DESKTOP_STARTUP_ID\" > {}' %u",
+        out.display()
+    );
+    let launched_with = |app: &UrlHandlerApp, options: &LaunchOptions| {
+        app.open_url_with_options(url.clone(), None, options)
+            .unwrap();
+        std::fs::read_to_string(&out).unwrap()
+    };
+
+    let app = app_with_exec(dir.path(), &exec);
+    assert_eq!(launched_with(&app, &options), "kairo-token \n");
+
+    // Only in the variable of the windowing system the token is for
+    let x11 = LaunchOptions {
+        activation_token: Some(ActivationToken::x11("kairo-token")),
+        ..options.clone()
+    };
+    assert_eq!(launched_with(&app, &x11), " kairo-token\n");
+
+    // Not passed without a token, nor to apps that opt out of startup notification
+    let no_token = LaunchOptions {
+        activation_token: None,
+        ..options.clone()
+    };
+    assert_eq!(launched_with(&app, &no_token), " \n");
+
+    let app = app_with_keys(dir.path(), &exec, "StartupNotify=false");
+    assert_eq!(launched_with(&app, &options), " \n");
+}
+
+#[test]
+fn test_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let urls: Vec<kairo_core::Url> = vec![
+        "https://github.com".parse().unwrap(),
+        "https://gitlab.com".parse().unwrap(),
+    ];
+    let options = LaunchOptions::default();
+
+    let app = app_with_exec(dir.path(), "kairo-missing-browser --new-tab %u");
+    assert_eq!(
+        app.commands(&urls, None, &options).unwrap(),
+        vec![
+            vec!["kairo-missing-browser", "--new-tab", "https://github.com/"],
+            vec!["kairo-missing-browser", "--new-tab", "https://gitlab.com/"],
+        ]
+    );
+    assert!(!app.uses_dbus(None, &LaunchOptions::default()).unwrap());
+
+    // Commands are wrapped in the terminal and the launch wrapper
+    let app = UrlHandlerApp {
+        wrapper: Some(kairo_core::LaunchWrapper::from_template("nice {command}").unwrap()),
+        ..app_with_keys(dir.path(), "w3m %U", "Terminal=true")
+    };
+    let options = LaunchOptions {
+        terminal: Some(kairo_core::Terminal::from_template("kitty {command}").unwrap()),
+        ..options
+    };
+    assert_eq!(
+        app.commands(&urls, None, &options).unwrap(),
+        vec![vec![
+            "nice",
+            "kitty",
+            "w3m",
+            "https://github.com/",
+            "https://gitlab.com/"
+        ]]
+    );
+}
+
+#[test]
+fn test_serialize() {
+    let apps = utils::handlers_for_scheme("http", &MimeApps::default()).unwrap();
+    let app = apps.iter().find(|app| app.appid == "firefox").unwrap();
+
+    let value = serde_json::to_value(app).unwrap();
+    assert_eq!(value["appid"], "firefox");
+    assert_eq!(value["name"], "Firefox");
+    assert_eq!(value["comment"], "Browse the World Wide Web");
+    assert_eq!(value["icon"], "firefox");
+    assert!(value.get("icon_path").is_none());
+    assert_eq!(
+        value["path"],
+        utils::entries_path()
+            .join("firefox.desktop")
+            .to_str()
+            .unwrap()
+    );
+    assert_eq!(value["is_default"], false);
+    assert_eq!(value["terminal"], false);
+    assert_eq!(
+        value["actions"][1],
+        serde_json::json!({ "id": "new-private-window", "name": "New Private Window" })
+    );
+}
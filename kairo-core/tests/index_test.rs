@@ -0,0 +1,128 @@
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use kairo_core::{HandlerIndex, MimeApps, UrlHandlerApp, Visibility};
+
+fn write_entry(path: &Path, name: &str, mime: &str, modified: SystemTime) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(
+        path,
+        format!(
+            "[Desktop Entry]\nType=Application\nName={name}\nExec={name} %u\nMimeType={mime}\n"
+        ),
+    )
+    .unwrap();
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+}
+
+fn ids(index: &HandlerIndex, search_paths: &[std::path::PathBuf]) -> Vec<String> {
+    index
+        .entries(search_paths)
+        .into_iter()
+        .map(|entry| entry.id.clone())
+        .collect()
+}
+
+#[test]
+fn test_update() {
+    let dir = tempfile::tempdir().unwrap();
+    let apps = dir.path().join("applications");
+    let search_paths = vec![apps.clone()];
+    let yesterday = SystemTime::now() - Duration::from_secs(86400);
+
+    write_entry(
+        &apps.join("browser.desktop"),
+        "browser",
+        "x-scheme-handler/http;",
+        yesterday,
+    );
+    fs::write(apps.join("invalid.desktop"), "not a desktop entry").unwrap();
+
+    let mut index = HandlerIndex::in_memory();
+    assert!(index.update(&search_paths));
+    assert_eq!(ids(&index, &search_paths), vec!["browser"]);
+    assert!(index.entries(&search_paths)[0].declares("x-scheme-handler/HTTP"));
+
+    // Nothing changed
+    assert!(!index.update(&search_paths));
+
+    // Changed files are parsed again
+    write_entry(
+        &apps.join("browser.desktop"),
+        "browser",
+        "x-scheme-handler/ftp;",
+        SystemTime::now(),
+    );
+    assert!(index.update(&search_paths));
+    let entries = index.entries(&search_paths);
+    assert!(!entries[0].declares("x-scheme-handler/http"));
+    assert!(entries[0].declares("x-scheme-handler/ftp"));
+
+    // New files and subdirectories are found
+    write_entry(
+        &apps.join("kde/konqueror.desktop"),
+        "konqueror",
+        "x-scheme-handler/http;",
+        yesterday,
+    );
+    assert!(index.update(&search_paths));
+    assert_eq!(ids(&index, &search_paths), vec!["browser", "kde-konqueror"]);
+
+    // Removed files are dropped
+    fs::remove_file(apps.join("browser.desktop")).unwrap();
+    assert!(index.update(&search_paths));
+    assert_eq!(ids(&index, &search_paths), vec!["kde-konqueror"]);
+
+    // Search paths that no longer exist are dropped
+    fs::remove_dir_all(&apps).unwrap();
+    assert!(index.update(&search_paths));
+    assert!(ids(&index, &search_paths).is_empty());
+}
+
+#[test]
+fn test_save() {
+    let dir = tempfile::tempdir().unwrap();
+    let apps = dir.path().join("applications");
+    let search_paths = vec![apps.clone()];
+    let cache = dir.path().join("cache/handlers.json");
+
+    write_entry(
+        &apps.join("browser.desktop"),
+        "browser",
+        "x-scheme-handler/http;",
+        SystemTime::now() - Duration::from_secs(60),
+    );
+
+    let mut index = HandlerIndex::open(&cache);
+    assert!(index.update(&search_paths));
+    index.save().unwrap();
+
+    let mut index = HandlerIndex::open(&cache);
+    assert!(!index.update(&search_paths));
+    assert_eq!(ids(&index, &search_paths), vec!["browser"]);
+
+    let scan = UrlHandlerApp::scan_scheme_with_index(
+        "http",
+        None,
+        Some(search_paths),
+        &MimeApps::default(),
+        &Visibility::default(),
+        &mut index,
+    )
+    .unwrap();
+    assert_eq!(scan.apps.len(), 1);
+    assert_eq!(scan.apps[0].appid, "browser");
+
+    // Invalid indexes are rebuilt
+    fs::write(&cache, "{").unwrap();
+    let mut index = HandlerIndex::open(&cache);
+    assert!(index.update(&[apps]));
+}
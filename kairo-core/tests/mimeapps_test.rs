@@ -0,0 +1,100 @@
+mod utils;
+
+use kairo_core::{MimeApps, mimeapps};
+
+fn mime_apps(desktops: &[&str]) -> MimeApps {
+    let dirs = [
+        utils::mimeapps_path().join("config"),
+        utils::mimeapps_path().join("data/applications"),
+    ];
+    let desktops = desktops.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+
+    MimeApps::from_paths(mimeapps::list_paths(dirs, &desktops))
+}
+
+fn handlers(scheme: &str, mime_apps: &MimeApps) -> Vec<(String, bool)> {
+    utils::handlers_for_scheme(scheme, mime_apps)
+        .unwrap()
+        .into_iter()
+        .map(|app| (app.appid, app.is_default))
+        .collect()
+}
+
+#[test]
+fn test_list_paths() {
+    let paths = mimeapps::list_paths(
+        ["/home/user/.config".into(), "/etc/xdg".into()],
+        &["KDE".to_string()],
+    );
+
+    assert_eq!(
+        paths,
+        [
+            "/home/user/.config/kde-mimeapps.list",
+            "/home/user/.config/mimeapps.list",
+            "/etc/xdg/kde-mimeapps.list",
+            "/etc/xdg/mimeapps.list",
+        ]
+        .map(std::path::PathBuf::from)
+    );
+}
+
+#[test]
+fn test_default_application() {
+    let mime_apps = mime_apps(&[]);
+
+    // The first installed default of the most important list wins
+    assert_eq!(
+        handlers("http", &mime_apps),
+        vec![
+            ("brave-browser".to_string(), true),
+            ("firefox".to_string(), false),
+        ]
+    );
+
+    // Falls back to the system defaults
+    assert_eq!(
+        handlers("https", &mime_apps)[0],
+        ("firefox".to_string(), true)
+    );
+}
+
+#[test]
+fn test_desktop_specific_defaults() {
+    let mime_apps = mime_apps(&["test"]);
+
+    assert_eq!(
+        handlers("https", &mime_apps)[0],
+        ("black-hole".to_string(), true)
+    );
+}
+
+#[test]
+fn test_added_and_removed_associations() {
+    let mime_apps = mime_apps(&[]);
+
+    // Removed by the user list even though the app declares the scheme
+    let associations = mime_apps.associations("x-scheme-handler/http");
+    assert!(!associations.is_associated("black-hole", true));
+
+    // Added by the user list, so the removal from the less important system list doesn't apply
+    let associations = mime_apps.associations("x-scheme-handler/https");
+    assert!(associations.is_associated("black-hole", true));
+    assert_eq!(
+        handlers("https", &mime_apps),
+        vec![
+            ("firefox".to_string(), true),
+            ("black-hole".to_string(), false),
+            ("brave-browser".to_string(), false),
+        ]
+    );
+
+    // Added associations make apps handle schemes they don't declare, ordered first
+    assert_eq!(
+        handlers("ipfs", &mime_apps),
+        vec![
+            ("firefox".to_string(), false),
+            ("brave-browser".to_string(), false),
+        ]
+    );
+}
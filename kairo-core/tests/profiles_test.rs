@@ -0,0 +1,191 @@
+mod utils;
+
+use freedesktop_desktop_entry as fde;
+use kairo_core::{BrowserProfile, LaunchOptions, MimeApps, ProfileDiscovery, UrlHandlerApp};
+
+fn discovery() -> ProfileDiscovery {
+    let profiles_path = utils::profiles_path();
+    ProfileDiscovery::new(profiles_path.join("home"), profiles_path.join("config"))
+}
+
+fn app(appid: &str) -> UrlHandlerApp {
+    let locales: [String; 0] = [];
+    let path = utils::entries_path().join(format!("{appid}.desktop"));
+    let de = fde::DesktopEntry::from_path(path, Some(&locales)).unwrap();
+    UrlHandlerApp::from_desktop_entry(de, &locales)
+}
+
+#[test]
+fn test_profiles() {
+    let discovery = discovery();
+
+    assert_eq!(
+        discovery.profiles("firefox"),
+        vec![
+            BrowserProfile {
+                id: "default-release".into(),
+                name: "default-release".into()
+            },
+            BrowserProfile {
+                id: "work".into(),
+                name: "work".into()
+            },
+        ]
+    );
+    assert_eq!(
+        discovery.profiles("brave-browser"),
+        vec![
+            BrowserProfile {
+                id: "Default".into(),
+                name: "Personal".into()
+            },
+            BrowserProfile {
+                id: "Profile 1".into(),
+                name: "Work".into()
+            },
+        ]
+    );
+    assert_eq!(discovery.profiles("google-chrome").len(), 1);
+    assert!(discovery.profiles("vivaldi-stable").is_empty());
+    assert!(discovery.profiles("black-hole").is_empty());
+
+    // Flatpak apps keep their profiles under ~/.var/app
+    assert_eq!(
+        discovery.profiles("org.mozilla.firefox"),
+        vec![
+            BrowserProfile {
+                id: "flatpak-default".into(),
+                name: "flatpak-default".into()
+            },
+            BrowserProfile {
+                id: "flatpak-work".into(),
+                name: "flatpak-work".into()
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_profile_handlers() {
+    let discovery = discovery();
+
+    let handlers = discovery.profile_handlers(&app("firefox"));
+    let handlers = handlers
+        .iter()
+        .map(|app| (app.appid.as_str(), app.name.as_str(), app.args.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        handlers,
+        vec![
+            (
+                "firefox@default-release",
+                "Firefox — default-release",
+                vec!["-P".to_string(), "default-release".to_string()]
+            ),
+            (
+                "firefox@work",
+                "Firefox — work",
+                vec!["-P".to_string(), "work".to_string()]
+            ),
+        ]
+    );
+
+    let handlers = discovery.profile_handlers(&app("brave-browser"));
+    let handlers = handlers
+        .iter()
+        .map(|app| (app.appid.as_str(), app.name.as_str(), app.args.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        handlers,
+        vec![
+            (
+                "brave-browser@default",
+                "Brave — Personal",
+                vec!["--profile-directory=Default".to_string()]
+            ),
+            (
+                "brave-browser@profile-1",
+                "Brave — Work",
+                vec!["--profile-directory=Profile 1".to_string()]
+            ),
+        ]
+    );
+
+    assert!(discovery.profile_handlers(&app("black-hole")).is_empty());
+}
+
+#[test]
+fn test_flatpak_profile_commands() {
+    let locales: [String; 0] = [];
+    let path = utils::profiles_path().join("applications/org.mozilla.firefox.desktop");
+    let de = fde::DesktopEntry::from_path(path, Some(&locales)).unwrap();
+    let app = UrlHandlerApp::from_desktop_entry(de, &locales);
+
+    let handlers = discovery().profile_handlers(&app);
+    assert_eq!(handlers.len(), 2);
+    let work = &handlers[1];
+    assert_eq!(work.appid, "org.mozilla.firefox@flatpak-work");
+
+    // The profile is passed to Firefox, not to flatpak, and outside the forwarded URLs
+    let urls = ["https://example.com".parse().unwrap()];
+    let options = LaunchOptions::default();
+    let flatpak = |args: &[&str]| {
+        [
+            "/usr/bin/flatpak",
+            "run",
+            "--branch=stable",
+            "--arch=x86_64",
+            "--command=firefox",
+            "--file-forwarding",
+            "org.mozilla.firefox",
+        ]
+        .iter()
+        .chain(args)
+        .map(|arg| arg.to_string())
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        work.commands(&urls, None, &options).unwrap(),
+        vec![flatpak(&[
+            "-P",
+            "flatpak-work",
+            "@@u",
+            "https://example.com/",
+            "@@"
+        ])]
+    );
+    assert_eq!(
+        work.commands(&urls, Some("new-window"), &options).unwrap(),
+        vec![flatpak(&[
+            "--new-window",
+            "-P",
+            "flatpak-work",
+            "@@u",
+            "https://example.com/",
+            "@@"
+        ])]
+    );
+}
+
+#[test]
+fn test_with_profiles() {
+    let apps = utils::handlers_for_scheme("http", &MimeApps::default()).unwrap();
+    let apps = discovery().with_profiles(apps);
+
+    let appids = apps
+        .iter()
+        .map(|app| app.appid.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        appids,
+        vec![
+            "black-hole",
+            "brave-browser",
+            "brave-browser@default",
+            "brave-browser@profile-1",
+            "firefox",
+            "firefox@default-release",
+            "firefox@work",
+        ]
+    );
+}
@@ -0,0 +1,95 @@
+use kairo_core::{Url, unwrap_redirects};
+
+fn unwrapped(url: &str) -> (String, Vec<&'static str>) {
+    let url: Url = url.parse().unwrap();
+    let unwrapped = unwrap_redirects(&url);
+    (unwrapped.url.to_string(), unwrapped.redirectors)
+}
+
+#[test]
+fn test_unwrap_known_redirectors() {
+    let cases = [
+        (
+            "https://eur01.safelinks.protection.outlook.com/?url=https%3A%2F%2Fgitlab.work.com%2Fgroup%2Fproject&data=05%7C01&reserved=0",
+            "https://gitlab.work.com/group/project",
+            "Outlook Safe Links",
+        ),
+        (
+            "https://www.google.com/url?sa=t&q=https://example.com/page%3Fid%3D1&usg=AOv",
+            "https://example.com/page?id=1",
+            "Google",
+        ),
+        (
+            "https://www.google.co.uk/url?q=https://example.com/",
+            "https://example.com/",
+            "Google",
+        ),
+        (
+            "https://www.youtube.com/redirect?event=video_description&q=https%3A%2F%2Fexample.com%2F",
+            "https://example.com/",
+            "YouTube",
+        ),
+        (
+            "https://www.linkedin.com/redir/redirect?url=https%3A%2F%2Fexample.com%2F",
+            "https://example.com/",
+            "LinkedIn",
+        ),
+        (
+            "https://slack-redir.net/link?url=https%3A%2F%2Fexample.com%2F",
+            "https://example.com/",
+            "Slack",
+        ),
+        (
+            "https://l.facebook.com/l.php?u=https%3A%2F%2Fexample.com%2Farticle&h=AT0",
+            "https://example.com/article",
+            "Facebook",
+        ),
+        (
+            "https://duckduckgo.com/l/?uddg=https%3A%2F%2Fexample.com%2F&rut=abc",
+            "https://example.com/",
+            "DuckDuckGo",
+        ),
+    ];
+
+    for (wrapped, expected, name) in cases {
+        assert_eq!(unwrapped(wrapped), (expected.to_string(), vec![name]));
+    }
+}
+
+#[test]
+fn test_unwrap_nested_redirectors() {
+    let (url, redirectors) = unwrapped(
+        "https://nam02.safelinks.protection.outlook.com/?url=https%3A%2F%2Fwww.google.com%2Furl%3Fq%3Dhttps%253A%252F%252Fexample.com%252F",
+    );
+
+    assert_eq!(url, "https://example.com/");
+    assert_eq!(redirectors, vec!["Outlook Safe Links", "Google"]);
+}
+
+#[test]
+fn test_unwrap_untouched_urls() {
+    let urls = [
+        "https://www.google.com/search?q=https://example.com",
+        "https://example.com/l.php?u=https%3A%2F%2Fother.com",
+        // Only web destinations are unwrapped
+        "https://www.google.com/url?q=javascript:alert(1)",
+        "https://slack-redir.net/link?url=not%20a%20url",
+        // Other sites of the same companies, and look-alike hosts and paths
+        "https://mail.google.com/url?q=https://example.com/",
+        "https://www.google.evil.example/url?q=https://example.com/",
+        "https://google.evil.example/url?q=https://example.com/",
+        "https://www.google.com/urls?q=https://example.com/",
+        "https://live.facebook.com/l.php?u=https%3A%2F%2Fexample.com%2F",
+        "https://l.facebook.com/l.php.evil?u=https%3A%2F%2Fexample.com%2F",
+        "https://music.youtube.com/redirect?q=https%3A%2F%2Fexample.com%2F",
+        "https://notyoutube.com/redirect?q=https%3A%2F%2Fexample.com%2F",
+        "https://evillinkedin.com/redir/redirect?url=https%3A%2F%2Fexample.com%2F",
+        "https://a.b.safelinks.protection.outlook.com/?url=https%3A%2F%2Fexample.com%2F",
+    ];
+
+    for url in urls {
+        let (unwrapped, redirectors) = unwrapped(url);
+        assert_eq!(unwrapped, url);
+        assert!(redirectors.is_empty());
+    }
+}
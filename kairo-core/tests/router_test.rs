@@ -0,0 +1,183 @@
+mod utils;
+
+use kairo_core::{Error, LaunchWrapper, MimeApps, Router, Rule, Url, UrlHandlerApp};
+
+fn http_apps() -> Vec<UrlHandlerApp> {
+    utils::handlers_for_scheme("http", &MimeApps::default()).unwrap()
+}
+
+fn routed_appid(router: &Router, url: &str, apps: &[UrlHandlerApp]) -> Option<String> {
+    let url: Url = url.parse().unwrap();
+    router
+        .route(&url, apps)
+        .map(|route| route.app.appid.clone())
+}
+
+#[test]
+fn test_from_file() {
+    let router = Router::from_file(&utils::config_path().join("rules.toml")).unwrap();
+    let apps = http_apps();
+
+    assert_eq!(router.rules().len(), 5);
+    assert_eq!(
+        routed_appid(&router, "https://gitlab.work.com/group/project", &apps).as_deref(),
+        Some("brave-browser")
+    );
+    assert_eq!(
+        routed_appid(&router, "https://wiki.work.com/tools/deploy", &apps).as_deref(),
+        Some("black-hole")
+    );
+    assert_eq!(
+        routed_appid(
+            &router,
+            "https://github.com/owner/repo/issues?tab=open",
+            &apps
+        )
+        .as_deref(),
+        Some("brave-browser")
+    );
+    // Falls through to the catch-all rule
+    assert_eq!(
+        routed_appid(&router, "https://wiki.work.com/other", &apps).as_deref(),
+        Some("firefox")
+    );
+    assert_eq!(
+        routed_appid(&router, "https://github.com/owner/repo/issues", &apps).as_deref(),
+        Some("firefox")
+    );
+    // Skips rules pointing to handlers that are not available
+    assert_eq!(
+        routed_appid(&router, "https://example.org", &apps).as_deref(),
+        Some("firefox")
+    );
+
+    // Routed apps are launched with the environment, working directory and wrapper of the rule
+    let url: Url = "https://gitlab.work.com".parse().unwrap();
+    let app = router.route(&url, &apps).unwrap().handler();
+    let home = std::path::PathBuf::from(std::env::var_os("HOME").unwrap());
+    assert_eq!(app.env["GTK_THEME"], "Adwaita:dark");
+    assert_eq!(app.working_dir, Some(home.join("work")));
+    assert!(app.wrapper.is_none());
+    let url: Url = "https://example.org".parse().unwrap();
+    assert!(router.route(&url, &apps).unwrap().handler().env.is_empty());
+    let url: Url = "https://wiki.work.com/tools/deploy".parse().unwrap();
+    assert_eq!(
+        router.route(&url, &apps).unwrap().handler().wrapper,
+        LaunchWrapper::preset("firejail")
+    );
+}
+
+#[test]
+fn test_from_file_invalid() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rules.toml");
+    std::fs::write(
+        &path,
+        "[[rules]]\nhandler = \"firefox\"\nregex = \"(unclosed\"\n",
+    )
+    .unwrap();
+
+    let err = Router::from_file(&path).unwrap_err();
+    assert!(matches!(err, Error::LoadRules { .. }), "{:?}", err);
+}
+
+#[test]
+fn test_from_file_relative_working_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rules.toml");
+    std::fs::write(
+        &path,
+        "[[rules]]\nhandler = \"firefox\"\nworking_dir = \"projects\"\n",
+    )
+    .unwrap();
+
+    let router = Router::from_file(&path).unwrap();
+    assert_eq!(
+        router.rules()[0].working_dir,
+        Some(dir.path().join("projects"))
+    );
+}
+
+#[test]
+fn test_rule_matches() {
+    let url: Url = "HTTPS://GitLab.Work.com/group/project?ref=main&tab=ci"
+        .parse()
+        .unwrap();
+
+    let rule = |f: fn(&mut Rule)| {
+        let mut rule = Rule {
+            handler: "firefox".into(),
+            ..Default::default()
+        };
+        f(&mut rule);
+        rule
+    };
+
+    assert!(rule(|_| {}).matches(&url));
+    assert!(rule(|r| r.scheme = Some("https".into())).matches(&url));
+    assert!(!rule(|r| r.scheme = Some("http".into())).matches(&url));
+    assert!(rule(|r| r.host = Some("gitlab.work.com".into())).matches(&url));
+    assert!(!rule(|r| r.host = Some("work.com".into())).matches(&url));
+    assert!(rule(|r| r.host_glob = Some("*.WORK.com".into())).matches(&url));
+    assert!(rule(|r| r.host_glob = Some("gitla?.*".into())).matches(&url));
+    assert!(!rule(|r| r.host_glob = Some("*.work.org".into())).matches(&url));
+    assert!(rule(|r| r.path_prefix = Some("/group/".into())).matches(&url));
+    assert!(!rule(|r| r.path_prefix = Some("/other".into())).matches(&url));
+    assert!(rule(|r| r.regex = Some(regex::Regex::new("project\\?ref=").unwrap())).matches(&url));
+    assert!(
+        rule(|r| {
+            r.query.insert("ref".into(), "main".into());
+            r.query.insert("tab".into(), "*".into());
+        })
+        .matches(&url)
+    );
+    assert!(
+        !rule(|r| {
+            r.query.insert("ref".into(), "dev*".into());
+        })
+        .matches(&url)
+    );
+    assert!(
+        !rule(|r| {
+            r.query.insert("missing".into(), "*".into());
+        })
+        .matches(&url)
+    );
+}
+
+#[test]
+fn test_route_with_action() {
+    let apps = http_apps();
+    let url: Url = "https://example.com".parse().unwrap();
+
+    let router = Router::new(vec![Rule {
+        handler: "firefox".into(),
+        action: Some("new-private-window".into()),
+        ..Default::default()
+    }]);
+    let route = router.route(&url, &apps).unwrap();
+    assert_eq!(route.app.appid, "firefox");
+    assert_eq!(route.action.unwrap().id, "new-private-window");
+
+    // Skips rules pointing to actions that are not available
+    let router = Router::new(vec![
+        Rule {
+            handler: "brave-browser".into(),
+            action: Some("new-private-window".into()),
+            ..Default::default()
+        },
+        Rule {
+            handler: "black-hole".into(),
+            ..Default::default()
+        },
+    ]);
+    let route = router.route(&url, &apps).unwrap();
+    assert_eq!(route.app.appid, "black-hole");
+    assert!(route.action.is_none());
+}
+
+#[test]
+fn test_route_without_rules() {
+    let router = Router::default();
+    assert!(routed_appid(&router, "https://example.com", &http_apps()).is_none());
+}
@@ -0,0 +1,212 @@
+mod utils;
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use kairo_core::{
+    AppAction,
+    AppConfig,
+    AppSettings,
+    Config,
+    DesktopEntries,
+    Error,
+    HandlerRegistry,
+    HandlerSource,
+    LaunchOptions,
+    LaunchWrapper,
+    MimeApps,
+    ProfileDiscovery,
+    Result,
+    Url,
+    UrlHandler,
+    UrlHandlerApp,
+};
+
+/// A pseudo-handler that records the URLs it opens and the options it receives.
+#[derive(Debug, Default)]
+struct Recorder {
+    opened: Mutex<Vec<(String, Option<String>)>>,
+    options: Mutex<Vec<LaunchOptions>>,
+}
+
+impl UrlHandler for Recorder {
+    fn id(&self) -> &str {
+        "recorder"
+    }
+
+    fn name(&self) -> &str {
+        "Recorder"
+    }
+
+    fn actions(&self) -> Vec<AppAction> {
+        vec![AppAction {
+            id: "twice".into(),
+            name: "Record Twice".into(),
+        }]
+    }
+
+    fn open(&self, urls: &[Url], action: Option<&str>, options: &LaunchOptions) -> Result<()> {
+        let mut opened = self.opened.lock().unwrap();
+        for url in urls {
+            opened.push((url.to_string(), action.map(str::to_string)));
+        }
+        self.options.lock().unwrap().push(options.clone());
+        Ok(())
+    }
+}
+
+/// Adds the recorder for every scheme.
+struct RecorderSource(Arc<Recorder>);
+
+impl HandlerSource for RecorderSource {
+    fn discover(&self, _scheme: &str, apps: &mut Vec<UrlHandlerApp>) -> Result<()> {
+        apps.push(UrlHandlerApp::from_handler(self.0.clone()));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_registry() {
+    let entries = DesktopEntries {
+        locales: None,
+        search_paths: Some(vec![utils::entries_path()]),
+        mime_apps: Some(MimeApps::default()),
+        visibility: Some(utils::visibility()),
+        index_path: None,
+    };
+
+    let registry = HandlerRegistry::new().with_source(entries.clone());
+    assert_eq!(registry.handlers_for_scheme("http").unwrap().len(), 3);
+    assert!(matches!(
+        registry.handlers_for_scheme("gemini"),
+        Err(Error::NoHandlersFound(_))
+    ));
+    assert!(matches!(
+        HandlerRegistry::new().handlers_for_scheme("http"),
+        Err(Error::NoHandlersFound(_))
+    ));
+
+    // Sources are chained in order
+    let recorder = Arc::new(Recorder::default());
+    let registry = registry.with_source(RecorderSource(recorder));
+    let apps = registry.handlers_for_scheme("http").unwrap();
+    assert_eq!(apps.len(), 4);
+    assert_eq!(apps[3].appid, "recorder");
+    assert_eq!(registry.handlers_for_scheme("gemini").unwrap().len(), 1);
+}
+
+#[test]
+fn test_app_settings() {
+    let entries = DesktopEntries {
+        locales: None,
+        search_paths: Some(vec![utils::entries_path()]),
+        mime_apps: Some(MimeApps::default()),
+        visibility: Some(utils::visibility()),
+        index_path: None,
+    };
+    let profiles_path = utils::profiles_path();
+    let discovery = ProfileDiscovery::new(profiles_path.join("home"), profiles_path.join("config"));
+    let settings = AppSettings {
+        apps: BTreeMap::from([
+            (
+                "brave-browser".into(),
+                AppConfig {
+                    env: BTreeMap::from([("GTK_THEME".into(), "Adwaita:dark".into())]),
+                    working_dir: Some("/tmp".into()),
+                },
+            ),
+            (
+                "brave-browser@profile-1".into(),
+                AppConfig {
+                    env: BTreeMap::from([("GTK_THEME".into(), "Adwaita".into())]),
+                    working_dir: Some("/".into()),
+                },
+            ),
+        ]),
+    };
+
+    let registry = HandlerRegistry::new()
+        .with_source(entries)
+        .with_source(Config::default())
+        .with_source(discovery)
+        .with_source(settings);
+    let apps = registry.handlers_for_scheme("http").unwrap();
+    let app = |appid: &str| apps.iter().find(|app| app.appid == appid).unwrap();
+
+    // Profiles get the settings of their browser, then their own
+    assert_eq!(app("brave-browser").env["GTK_THEME"], "Adwaita:dark");
+    assert_eq!(
+        app("brave-browser@default").env["GTK_THEME"],
+        "Adwaita:dark"
+    );
+    assert_eq!(
+        app("brave-browser@default").working_dir.as_deref(),
+        Some(Path::new("/tmp"))
+    );
+    assert_eq!(app("brave-browser@profile-1").env["GTK_THEME"], "Adwaita");
+    assert_eq!(
+        app("brave-browser@profile-1").working_dir.as_deref(),
+        Some(Path::new("/"))
+    );
+    assert!(app("firefox").env.is_empty());
+}
+
+#[test]
+fn test_from_handler() {
+    let recorder = Arc::new(Recorder::default());
+    let app = UrlHandlerApp::from_handler(recorder.clone());
+
+    assert_eq!(app.appid, "recorder");
+    assert_eq!(app.name, "Recorder");
+    assert_eq!(app.actions.len(), 1);
+    assert_eq!(UrlHandler::id(&app), "recorder");
+
+    let url: Url = "https://github.com".parse().unwrap();
+    app.open_url(url.clone()).unwrap();
+    app.open_url_with_action(url, Some("twice")).unwrap();
+    assert_eq!(
+        *recorder.opened.lock().unwrap(),
+        vec![
+            ("https://github.com/".to_string(), None),
+            ("https://github.com/".to_string(), Some("twice".to_string())),
+        ]
+    );
+
+    // The settings of the app are passed on to the handler, over the ones from the options
+    let app = app.with_env(
+        &BTreeMap::from([("APP".into(), "1".into()), ("SHARED".into(), "app".into())]),
+        Some(Path::new("/tmp")),
+    );
+    let app = UrlHandlerApp {
+        wrapper: Some(LaunchWrapper::from_template("firejail").unwrap()),
+        ..app
+    };
+    let options = LaunchOptions {
+        env: BTreeMap::from([
+            ("OPTION".into(), "1".into()),
+            ("SHARED".into(), "option".into()),
+        ]),
+        working_dir: Some("/".into()),
+        wrapper: Some(LaunchWrapper::from_template("systemd-run").unwrap()),
+        ..Default::default()
+    };
+    app.open_url_with_options("https://github.com".parse().unwrap(), None, &options)
+        .unwrap();
+    let received = recorder.options.lock().unwrap().pop().unwrap();
+    assert_eq!(
+        received.env,
+        BTreeMap::from([
+            ("APP".into(), "1".into()),
+            ("OPTION".into(), "1".into()),
+            ("SHARED".into(), "app".into()),
+        ])
+    );
+    assert_eq!(received.working_dir.as_deref(), Some(Path::new("/tmp")));
+    assert_eq!(
+        received.wrapper,
+        LaunchWrapper::from_template("firejail").ok()
+    );
+}
@@ -0,0 +1,139 @@
+mod utils;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+use kairo_core::{Error, LaunchOptions, MimeApps, Terminal};
+
+fn command(terminal: &Terminal) -> Vec<String> {
+    let (cmd, args) = terminal.command("w3m".into(), vec!["https://example.com/".into()]);
+    [vec![cmd], args].concat()
+}
+
+fn install(dir: &Path, programs: &[&str]) {
+    for program in programs {
+        let path = dir.join(program);
+        fs::write(&path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
+
+#[test]
+fn test_from_template() {
+    let terminal = Terminal::from_template("kitty --single-instance {command} --hold").unwrap();
+    assert_eq!(
+        command(&terminal),
+        [
+            "kitty",
+            "--single-instance",
+            "w3m",
+            "https://example.com/",
+            "--hold"
+        ]
+    );
+
+    // The command is appended when there is no placeholder
+    let terminal = Terminal::from_template("foot --title 'Web browser'").unwrap();
+    assert_eq!(
+        command(&terminal),
+        [
+            "foot",
+            "--title",
+            "Web browser",
+            "w3m",
+            "https://example.com/"
+        ]
+    );
+
+    for template in ["", "  ", "kitty 'unmatched"] {
+        let err = Terminal::from_template(template).unwrap_err();
+        assert!(matches!(err, Error::InvalidTerminalTemplate(_)), "{err:?}");
+    }
+}
+
+#[test]
+fn test_detect() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = dir.path().as_os_str();
+
+    assert_eq!(Terminal::detect_from(paths, None), None);
+
+    // Falls back to the known emulators, in order of preference
+    install(dir.path(), &["xterm", "alacritty"]);
+    let terminal = Terminal::detect_from(paths, None).unwrap();
+    assert_eq!(
+        command(&terminal),
+        ["alacritty", "-e", "w3m", "https://example.com/"]
+    );
+
+    // $TERMINAL takes precedence, as a program or a template
+    let terminal = Terminal::detect_from(paths, Some("wezterm")).unwrap();
+    assert_eq!(
+        command(&terminal),
+        ["wezterm", "start", "--", "w3m", "https://example.com/"]
+    );
+    let terminal = Terminal::detect_from(paths, Some("my-term")).unwrap();
+    assert_eq!(
+        command(&terminal),
+        ["my-term", "-e", "w3m", "https://example.com/"]
+    );
+    let terminal = Terminal::detect_from(paths, Some("kitty --single-instance")).unwrap();
+    assert_eq!(
+        command(&terminal),
+        [
+            "kitty",
+            "--single-instance",
+            "--",
+            "w3m",
+            "https://example.com/"
+        ]
+    );
+    let terminal = Terminal::detect_from(paths, Some("/usr/bin/konsole --separate")).unwrap();
+    assert_eq!(
+        command(&terminal),
+        [
+            "/usr/bin/konsole",
+            "--separate",
+            "-e",
+            "w3m",
+            "https://example.com/"
+        ]
+    );
+    let terminal = Terminal::detect_from(paths, Some("my-term --run {command}")).unwrap();
+    assert_eq!(
+        command(&terminal),
+        ["my-term", "--run", "w3m", "https://example.com/"]
+    );
+
+    // xdg-terminal-exec is always preferred
+    install(dir.path(), &["xdg-terminal-exec"]);
+    let terminal = Terminal::detect_from(paths, Some("wezterm")).unwrap();
+    assert_eq!(
+        command(&terminal),
+        ["xdg-terminal-exec", "w3m", "https://example.com/"]
+    );
+}
+
+#[test]
+fn test_open_terminal_app() {
+    let apps = utils::handlers_for_scheme("mailto", &MimeApps::default()).unwrap();
+    let app = apps.iter().find(|app| app.appid == "mutt").unwrap();
+    assert!(app.terminal);
+
+    let url = "mailto:someone@example.com"
+        .parse::<kairo_core::Url>()
+        .unwrap();
+    let options = |template: &str| LaunchOptions {
+        terminal: Some(Terminal::from_template(template).unwrap()),
+        ..Default::default()
+    };
+
+    let result = app.open_url_with_options(url.clone(), None, &options("env {command}"));
+    assert!(result.is_ok(), "{result:?}");
+
+    // The command runs through the terminal
+    let result = app.open_url_with_options(url, None, &options("sh -c 'exit 3' {command}"));
+    assert!(matches!(
+        result,
+        Err(Error::OpenUrl(appid, status)) if appid == "mutt" && status.code() == Some(3)
+    ));
+}
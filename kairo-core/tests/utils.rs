@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
 use freedesktop_desktop_entry as fde;
+use kairo_core::{HandlerIndex, MimeApps, UrlHandlerApp, Visibility};
 
 const CARGO_MANIFEST_DIR: &str = env!("CARGO_MANIFEST_DIR");
 
@@ -8,6 +9,65 @@ pub fn entries_path() -> PathBuf {
     PathBuf::from(format!("{}/tests/entries", CARGO_MANIFEST_DIR))
 }
 
+#[allow(dead_code)]
+pub fn config_path() -> PathBuf {
+    PathBuf::from(format!("{}/tests/config", CARGO_MANIFEST_DIR))
+}
+
+#[allow(dead_code)]
+pub fn profiles_path() -> PathBuf {
+    PathBuf::from(format!("{}/tests/profiles", CARGO_MANIFEST_DIR))
+}
+
+#[allow(dead_code)]
+pub fn mimeapps_path() -> PathBuf {
+    PathBuf::from(format!("{}/tests/mimeapps", CARGO_MANIFEST_DIR))
+}
+
+#[allow(dead_code)]
+pub fn visibility_path() -> PathBuf {
+    PathBuf::from(format!("{}/tests/visibility", CARGO_MANIFEST_DIR))
+}
+
+#[allow(dead_code)]
+pub fn desktop_ids_path() -> PathBuf {
+    PathBuf::from(format!("{}/tests/desktop_ids", CARGO_MANIFEST_DIR))
+}
+
+#[allow(dead_code)]
+pub fn bin_path() -> PathBuf {
+    PathBuf::from(format!("{}/tests/bin", CARGO_MANIFEST_DIR))
+}
+
+/// Visibility rules for no desktop in particular, with only the stub programs in `PATH`.
+#[allow(dead_code)]
+pub fn visibility() -> Visibility {
+    Visibility::new(&[], bin_path())
+}
+
+/// Finds the handlers for the scheme in the test entries, without touching the user's
+/// associations or handler index.
+#[allow(dead_code)]
+pub fn handlers_for_scheme(
+    scheme: &str,
+    mime_apps: &MimeApps,
+) -> kairo_core::Result<Vec<UrlHandlerApp>> {
+    let scan = UrlHandlerApp::scan_scheme_with_index(
+        scheme,
+        None,
+        Some(vec![entries_path()]),
+        mime_apps,
+        &visibility(),
+        &mut HandlerIndex::in_memory(),
+    )?;
+
+    match scan.apps.is_empty() {
+        true => Err(kairo_core::Error::NoHandlersFound(scheme.to_string())),
+        false => Ok(scan.apps),
+    }
+}
+
+#[allow(dead_code)]
 pub fn black_hole_de<L>(locales: Option<&[L]>) -> fde::DesktopEntry
 where
     L: AsRef<str>,
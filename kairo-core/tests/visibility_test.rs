@@ -0,0 +1,125 @@
+mod utils;
+
+use freedesktop_desktop_entry as fde;
+use kairo_core::{Exclusion, HandlerIndex, MimeApps, UrlHandlerApp, Visibility};
+
+fn entry(keys: &str) -> fde::DesktopEntry {
+    let locales: [String; 0] = [];
+    let entry = format!("[Desktop Entry]\nType=Application\nName=Test\nExec=app %u\n{keys}\n");
+    fde::DesktopEntry::from_str("/tmp/test.desktop", &entry, Some(&locales)).unwrap()
+}
+
+fn scan(visibility: &Visibility, mime_apps: &MimeApps) -> (Vec<String>, Vec<(String, Exclusion)>) {
+    let scan = UrlHandlerApp::scan_scheme_with_index(
+        "http",
+        None,
+        Some(vec![utils::visibility_path()]),
+        mime_apps,
+        visibility,
+        &mut HandlerIndex::in_memory(),
+    )
+    .unwrap();
+
+    let mut apps = scan
+        .apps
+        .into_iter()
+        .map(|app| app.appid)
+        .collect::<Vec<_>>();
+    apps.sort();
+    let mut excluded = scan
+        .excluded
+        .into_iter()
+        .map(|entry| (entry.appid, entry.reason))
+        .collect::<Vec<_>>();
+    excluded.sort_by(|a, b| a.0.cmp(&b.0));
+
+    (apps, excluded)
+}
+
+#[test]
+fn test_check() {
+    let dir = tempfile::tempdir().unwrap();
+    let visibility = Visibility::new(&["ubuntu", "GNOME"], dir.path());
+
+    assert_eq!(visibility.check(&entry("")), Ok(()));
+    assert_eq!(
+        visibility.check(&entry("Hidden=true")),
+        Err(Exclusion::Hidden)
+    );
+    assert_eq!(
+        visibility.check(&entry("NoDisplay=true")),
+        Err(Exclusion::NoDisplay)
+    );
+
+    // Desktops are compared case-insensitively
+    assert_eq!(visibility.check(&entry("OnlyShowIn=gnome;")), Ok(()));
+    assert_eq!(
+        visibility.check(&entry("OnlyShowIn=KDE;LXQt;")),
+        Err(Exclusion::OnlyShowIn(vec!["KDE".into(), "LXQt".into()]))
+    );
+    assert_eq!(visibility.check(&entry("NotShowIn=KDE;")), Ok(()));
+    assert_eq!(
+        visibility.check(&entry("NotShowIn=GNOME;")),
+        Err(Exclusion::NotShowIn(vec!["GNOME".into()]))
+    );
+
+    // TryExec is looked up in the PATH, or checked directly if it's a path
+    assert_eq!(
+        visibility.check(&entry("TryExec=browser")),
+        Err(Exclusion::TryExecNotFound("browser".into()))
+    );
+    let program = dir.path().join("browser");
+    std::fs::write(&program, "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(
+        &program,
+        std::os::unix::fs::PermissionsExt::from_mode(0o755),
+    )
+    .unwrap();
+    assert_eq!(visibility.check(&entry("TryExec=browser")), Ok(()));
+    assert_eq!(
+        visibility.check(&entry(&format!("TryExec={}", program.display()))),
+        Ok(())
+    );
+
+    // Entries restricted to a desktop are hidden when it's unknown
+    let visibility = Visibility::new(&[], dir.path());
+    assert!(visibility.check(&entry("OnlyShowIn=GNOME;")).is_err());
+}
+
+#[test]
+fn test_scan_scheme() {
+    let visibility = Visibility::new(&["KDE"], "");
+    let (apps, excluded) = scan(&visibility, &MimeApps::default());
+
+    assert_eq!(apps, ["visible"]);
+    assert_eq!(
+        excluded,
+        [
+            (
+                "gnome-only".to_string(),
+                Exclusion::OnlyShowIn(vec!["GNOME".into()])
+            ),
+            ("hidden".to_string(), Exclusion::Hidden),
+            ("no-display".to_string(), Exclusion::NoDisplay),
+            (
+                "not-in-kde".to_string(),
+                Exclusion::NotShowIn(vec!["KDE".into()])
+            ),
+            (
+                "try-exec".to_string(),
+                Exclusion::TryExecNotFound("kairo-missing-browser".into())
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_scan_scheme_explicit_no_display() {
+    let visibility = Visibility::new(&["GNOME"], "");
+    let mime_apps = MimeApps::from_paths([utils::visibility_path().join("mimeapps.list")]);
+    let (apps, excluded) = scan(&visibility, &mime_apps);
+
+    // Explicitly associated entries are kept even if they are not displayed
+    assert_eq!(apps, ["gnome-only", "no-display", "not-in-kde", "visible"]);
+    assert_eq!(excluded.len(), 2);
+}
@@ -0,0 +1,202 @@
+mod utils;
+
+use std::path::Path;
+
+use kairo_core::{
+    Error,
+    LaunchOptions,
+    LaunchWrapper,
+    MimeApps,
+    ProgramRole,
+    UrlHandlerApp,
+    wrapper,
+};
+
+fn command(wrapper: &LaunchWrapper) -> Vec<String> {
+    let (cmd, args) = wrapper.command(
+        "firefox",
+        Path::new("/usr/share/applications/firefox.desktop"),
+        "firefox".into(),
+        vec!["https://example.com/".into()],
+    );
+    [vec![cmd], args].concat()
+}
+
+#[test]
+fn test_from_template() {
+    let wrapper =
+        LaunchWrapper::from_template("runner --id={appid} --entry {desktop_file} -- {command}")
+            .unwrap();
+    assert_eq!(
+        command(&wrapper),
+        [
+            "runner",
+            "--id=firefox",
+            "--entry",
+            "/usr/share/applications/firefox.desktop",
+            "--",
+            "firefox",
+            "https://example.com/"
+        ]
+    );
+
+    // The command is appended when there is no placeholder
+    let wrapper = LaunchWrapper::from_template("firejail --private").unwrap();
+    assert_eq!(
+        command(&wrapper),
+        ["firejail", "--private", "firefox", "https://example.com/"]
+    );
+
+    for template in ["", "  ", "runner 'unmatched"] {
+        let err = LaunchWrapper::from_template(template).unwrap_err();
+        assert!(matches!(err, Error::InvalidWrapperTemplate(_)), "{err:?}");
+    }
+}
+
+#[test]
+fn test_presets() {
+    for name in ["systemd-run", "app2unit", "uwsm", "firejail", "bwrap"] {
+        let wrapper = LaunchWrapper::preset(name).unwrap();
+        let wrapped = command(&wrapper);
+        assert_eq!(wrapped[0], name);
+        assert!(wrapped.ends_with(&["firefox".into(), "https://example.com/".into()]));
+        assert_eq!(LaunchWrapper::parse(name).unwrap(), wrapper);
+    }
+    assert!(LaunchWrapper::preset("unknown").is_none());
+
+    // firejail applies the sandbox profile of the launched program
+    assert_eq!(
+        command(&LaunchWrapper::preset("firejail").unwrap()),
+        [
+            "firejail",
+            "--quiet",
+            "--",
+            "firefox",
+            "https://example.com/"
+        ]
+    );
+
+    // bwrap exposes the home directory and the display servers to the sandboxed app
+    let home = std::env::var("HOME").unwrap_or_default();
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_default();
+    let wayland_socket = wrapper::wayland_socket(&runtime_dir)
+        .to_string_lossy()
+        .into_owned();
+    assert_eq!(
+        command(&LaunchWrapper::preset("bwrap").unwrap()),
+        [
+            "bwrap",
+            "--ro-bind",
+            "/",
+            "/",
+            "--dev",
+            "/dev",
+            "--proc",
+            "/proc",
+            "--tmpfs",
+            "/tmp",
+            "--ro-bind-try",
+            "/tmp/.X11-unix",
+            "/tmp/.X11-unix",
+            "--bind-try",
+            &home,
+            &home,
+            "--bind-try",
+            &runtime_dir,
+            &runtime_dir,
+            "--bind-try",
+            &wayland_socket,
+            &wayland_socket,
+            "--unshare-all",
+            "--share-net",
+            "--die-with-parent",
+            "--",
+            "firefox",
+            "https://example.com/"
+        ]
+    );
+
+    // Units are placed in a scope named after the app
+    let wrapped = command(&LaunchWrapper::preset("systemd-run").unwrap());
+    let unit = wrapped
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--unit="))
+        .unwrap();
+    assert!(unit.starts_with("app-kairo-firefox-"), "{unit}");
+    assert!(unit.ends_with(".scope"), "{unit}");
+
+    // Other values are templates
+    let wrapper = LaunchWrapper::parse("nice -n 10").unwrap();
+    assert_eq!(
+        command(&wrapper),
+        ["nice", "-n", "10", "firefox", "https://example.com/"]
+    );
+}
+
+#[test]
+fn test_unit_name() {
+    let unit = wrapper::unit_name("org.mozilla.firefox");
+    assert!(unit.starts_with("app-kairo-org.mozilla.firefox-"), "{unit}");
+
+    // Dashes separate the parts of the name, so the ones in the app ID are escaped
+    let unit = wrapper::unit_name("brave-browser@work");
+    assert!(
+        unit.starts_with("app-kairo-brave\\x2dbrowser\\x40work-"),
+        "{unit}"
+    );
+}
+
+#[test]
+fn test_open_with_wrapper() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let apps = utils::handlers_for_scheme("http", &MimeApps::default()).unwrap();
+    let app = apps.iter().find(|app| app.appid == "black-hole").unwrap();
+    let options = |template: &str| LaunchOptions {
+        detach: false,
+        wrapper: Some(LaunchWrapper::from_template(template).unwrap()),
+        ..Default::default()
+    };
+
+    let template = format!("sh -c 'echo \"$0\" > {}' {{appid}}", out.display());
+    app.open_url_with_options(
+        "https://example.com".parse().unwrap(),
+        None,
+        &options(&template),
+    )
+    .unwrap();
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), "black-hole\n");
+
+    // A missing wrapper is reported like a missing command
+    let err = app
+        .open_url_with_options(
+            "https://example.com".parse().unwrap(),
+            None,
+            &options("kairo-missing-wrapper {command}"),
+        )
+        .unwrap_err();
+    assert!(
+        matches!(
+            &err,
+            Error::ProgramNotFound { program, role: ProgramRole::Wrapper, .. }
+                if program == "kairo-missing-wrapper"
+        ),
+        "{err:?}"
+    );
+    assert!(err.to_string().contains("`wrapper`"), "{err}");
+
+    // The wrapper of the app takes precedence
+    let app = UrlHandlerApp {
+        wrapper: Some(LaunchWrapper::from_template("sh -c 'exit 5'").unwrap()),
+        ..app.clone()
+    };
+    let result = app.open_url_with_options(
+        "https://example.com".parse().unwrap(),
+        None,
+        &options(&template),
+    );
+    assert!(matches!(
+        result,
+        Err(Error::OpenUrl(_, status)) if status.code() == Some(5)
+    ));
+}
@@ -0,0 +1,130 @@
+use iced::window::{
+    Window,
+    raw_window_handle::{RawDisplayHandle, RawWindowHandle},
+};
+use kairo_core::ActivationToken;
+
+/// Requests a fresh activation token for the window from the compositor, so the app launched
+/// with it gets the focus.
+///
+/// Only Wayland's [xdg-activation](https://wayland.app/protocols/xdg-activation-v1) protocol is
+/// supported. Returns `None` elsewhere, or if the compositor doesn't support the protocol.
+pub fn request_token(window: &dyn Window) -> Option<ActivationToken> {
+    let display = window.display_handle().ok()?.as_raw();
+    let surface = window.window_handle().ok()?.as_raw();
+    match (display, surface) {
+        (RawDisplayHandle::Wayland(display), RawWindowHandle::Wayland(surface)) => {
+            wayland::request_token(display.display.as_ptr(), surface.surface.as_ptr())
+                .inspect_err(|e| log::warn!("Failed to request an activation token: {e}"))
+                .ok()
+                .flatten()
+        }
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod wayland {
+    use std::ffi::c_void;
+
+    use kairo_core::ActivationToken;
+    use wayland_backend::client::{Backend, ObjectId};
+    use wayland_client::{
+        Connection,
+        Dispatch,
+        Proxy,
+        QueueHandle,
+        globals::{GlobalListContents, registry_queue_init},
+        protocol::{wl_registry::WlRegistry, wl_surface::WlSurface},
+    };
+    use wayland_protocols::xdg::activation::v1::client::{
+        xdg_activation_token_v1::{self, XdgActivationTokenV1},
+        xdg_activation_v1::XdgActivationV1,
+    };
+
+    /// Receives the token once the compositor issued it.
+    #[derive(Default)]
+    struct State {
+        token: Option<String>,
+    }
+
+    /// Requests the token on a queue of its own, on the connection of the window.
+    pub fn request_token(
+        display: *mut c_void,
+        surface: *mut c_void,
+    ) -> Result<Option<ActivationToken>, Box<dyn std::error::Error>> {
+        // SAFETY: the display and the surface come from the handles of a live window, which
+        // outlives this call
+        let backend = unsafe { Backend::from_foreign_display(display.cast()) };
+        let connection = Connection::from_backend(backend);
+        let surface_id = unsafe { ObjectId::from_ptr(WlSurface::interface(), surface.cast())? };
+        let surface = WlSurface::from_id(&connection, surface_id)?;
+
+        let (globals, mut queue) = registry_queue_init::<State>(&connection)?;
+        let handle = queue.handle();
+        let activation: XdgActivationV1 = globals.bind(&handle, 1..=1, ())?;
+        let token = activation.get_activation_token(&handle, ());
+        token.set_surface(&surface);
+        token.commit();
+
+        let mut state = State::default();
+        queue.roundtrip(&mut state)?;
+        token.destroy();
+        activation.destroy();
+
+        Ok(state.token.map(ActivationToken::wayland))
+    }
+
+    impl Dispatch<WlRegistry, GlobalListContents> for State {
+        fn event(
+            _: &mut Self,
+            _: &WlRegistry,
+            _: <WlRegistry as Proxy>::Event,
+            _: &GlobalListContents,
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<XdgActivationV1, ()> for State {
+        fn event(
+            _: &mut Self,
+            _: &XdgActivationV1,
+            _: <XdgActivationV1 as Proxy>::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<XdgActivationTokenV1, ()> for State {
+        fn event(
+            state: &mut Self,
+            _: &XdgActivationTokenV1,
+            event: xdg_activation_token_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            if let xdg_activation_token_v1::Event::Done { token } = event {
+                state.token = Some(token);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod wayland {
+    use std::ffi::c_void;
+
+    use kairo_core::ActivationToken;
+
+    pub fn request_token(
+        _display: *mut c_void,
+        _surface: *mut c_void,
+    ) -> Result<Option<ActivationToken>, std::convert::Infallible> {
+        Ok(None)
+    }
+}
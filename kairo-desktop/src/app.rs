@@ -3,28 +3,54 @@ use iced::{
     Background,
     Element,
     Length,
+    Subscription,
     Task,
     Theme,
     advanced::graphics::text::cosmic_text::skrifa::raw::collections::int_set::Domain,
     border,
-    widget::{button, center, column, container, image, row, scrollable, svg, text, tooltip},
+    keyboard,
+    widget::{
+        button,
+        center,
+        checkbox,
+        column,
+        container,
+        image,
+        row,
+        scrollable,
+        svg,
+        text,
+        tooltip,
+    },
     window,
 };
-use kairo_core::{Url, UrlHandlerApp};
+use kairo_core::{
+    ActivationToken,
+    AppAction,
+    Choice,
+    ChoiceKey,
+    ChoiceStore,
+    Config,
+    LaunchOptions,
+    Url,
+    UrlHandlerApp,
+};
 use unicode_segmentation::UnicodeSegmentation;
 
+use crate::activation;
+
 // TODO: fetch from cargo metadata
 const APP_ID: &str = "io.github.aelesbao.Kairo";
 
 #[cfg(target_os = "macos")]
-const WIN_SIZE: [f32; 2] = [640.0, 230.0];
+const WIN_SIZE: [f32; 2] = [640.0, 260.0];
 #[cfg(not(target_os = "macos"))]
-const WIN_SIZE: [f32; 2] = [640.0, 210.0];
+const WIN_SIZE: [f32; 2] = [640.0, 240.0];
 #[cfg(target_os = "macos")]
-const WIN_MIN_SIZE: [f32; 2] = [480.0, 230.0];
+const WIN_MIN_SIZE: [f32; 2] = [480.0, 260.0];
 #[cfg(not(target_os = "macos"))]
-const WIN_MIN_SIZE: [f32; 2] = [480.0, 210.0];
-const WIN_MAX_SIZE: [f32; 2] = [1280.0, 210.0];
+const WIN_MIN_SIZE: [f32; 2] = [480.0, 240.0];
+const WIN_MAX_SIZE: [f32; 2] = [1280.0, 240.0];
 
 const APP_FONT_SIZE: u32 = 12;
 const URL_FONT_SIZE: u32 = 14;
@@ -38,14 +64,26 @@ const ICON_SIZE: u16 = 64;
 
 const UNKOWN_APP_ICON_BYTES: &[u8] = include_bytes!("../assets/unknown.svg");
 
-pub fn run(url: Url, apps: Vec<UrlHandlerApp>, explain: bool) -> iced::Result {
+pub fn run(
+    urls: Vec<Url>,
+    removed: Vec<String>,
+    apps: Vec<UrlHandlerApp>,
+    store: ChoiceStore,
+    options: LaunchOptions,
+    config: Config,
+    explain: bool,
+) -> iced::Result {
     log::info!("Launching UI for URL handler selection");
-    application(url, apps, explain).run()
+    application(urls, removed, apps, store, options, config, explain).run()
 }
 
 fn application(
-    url: Url,
+    urls: Vec<Url>,
+    removed: Vec<String>,
     apps: Vec<UrlHandlerApp>,
+    store: ChoiceStore,
+    options: LaunchOptions,
+    config: Config,
     explain: bool,
 ) -> iced::Application<impl iced::Program<Message = Message>> {
     let settings = iced::Settings {
@@ -65,11 +103,22 @@ fn application(
     };
 
     iced::application(
-        move || App::new(url.clone(), apps.clone(), explain),
+        move || {
+            App::new(
+                urls.clone(),
+                removed.clone(),
+                apps.clone(),
+                store.clone(),
+                options.clone(),
+                &config,
+                explain,
+            )
+        },
         App::update,
         App::view,
     )
     .title(App::title)
+    .subscription(App::subscription)
     .theme(App::theme)
     .settings(settings)
     .window(window)
@@ -99,18 +148,76 @@ fn platform_settings(_application_id: String) -> window::settings::PlatformSpeci
 
 #[derive(Debug, Clone)]
 enum Message {
-    OpenWithApp(UrlHandlerApp),
+    /// Opens the URLs with the target at the given index.
+    OpenWithApp(usize),
+    /// Launches the target at the given index, with the activation token requested for it.
+    Launch(usize, Option<ActivationToken>),
+    OpenPreselected,
+    RememberToggled(bool),
+    Cancel,
 }
 
 struct App {
-    url: Url,
-    apps: Vec<UrlHandlerApp>,
+    /// The URLs to open, all with the selected target.
+    urls: Vec<Url>,
+    /// The tracking parts removed from the URLs.
+    removed: Vec<String>,
+    /// Every app and action the URLs can be opened with.
+    targets: Vec<(UrlHandlerApp, Option<AppAction>)>,
+    store: ChoiceStore,
+    options: LaunchOptions,
+    /// Whether the first target was the last one used for the first URL.
+    preselected: bool,
+    remember: bool,
+    theme: Theme,
     explain: bool,
 }
 
 impl App {
-    fn new(url: Url, apps: Vec<UrlHandlerApp>, explain: bool) -> (Self, Task<Message>) {
-        let app = Self { url, apps, explain };
+    fn new(
+        urls: Vec<Url>,
+        removed: Vec<String>,
+        apps: Vec<UrlHandlerApp>,
+        store: ChoiceStore,
+        options: LaunchOptions,
+        config: &Config,
+        explain: bool,
+    ) -> (Self, Task<Message>) {
+        let mut targets = apps
+            .iter()
+            .flat_map(|app| {
+                app.launch_targets()
+                    .map(|action| (app.clone(), action.cloned()))
+            })
+            .filter(|(_, action)| config.picker.show_actions || action.is_none())
+            .collect::<Vec<_>>();
+
+        // Show the last used app first
+        let preselected = store.lookup(&urls[0]).and_then(|(_, choice)| {
+            targets.iter().position(|(app, action)| {
+                app.appid == choice.handler
+                    && action.as_ref().map(|action| &action.id) == choice.action.as_ref()
+            })
+        });
+        if let Some(index) = preselected {
+            targets[..=index].rotate_right(1);
+        }
+
+        let app = Self {
+            urls,
+            removed,
+            targets,
+            store,
+            options,
+            preselected: preselected.is_some(),
+            remember: false,
+            theme: config
+                .theme
+                .as_deref()
+                .and_then(find_theme)
+                .unwrap_or(Theme::TokyoNight),
+            explain,
+        };
         (app, Task::none())
     }
 
@@ -120,47 +227,129 @@ impl App {
 
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
-            Message::OpenWithApp(app) => match app.open_url(self.url.clone()) {
-                Ok(_) => iced::exit(),
-                Err(e) => {
-                    // TODO: display error message on UI
-                    log::error!("Failed to open URL with '{}': {}", app.name, e);
-                    let code = match e {
-                        kairo_core::Error::OpenUrl(_, status) => status.code(),
-                        _ => None,
-                    };
-                    std::process::exit(code.unwrap_or(1));
+            Message::OpenPreselected => self.update(Message::OpenWithApp(0)),
+            Message::RememberToggled(remember) => {
+                self.remember = remember;
+                Task::none()
+            }
+            Message::Cancel => iced::exit(),
+            Message::OpenWithApp(index) => match self.targets.get(index).cloned() {
+                Some((app, action)) => {
+                    self.remember_choice(&app, action.as_ref());
+                    // Tokens can only be used once, so a fresh one is requested for the picker's
+                    // window, falling back to the one Kairo was started with
+                    window::latest()
+                        .then(|id| match id {
+                            Some(id) => window::run(id, activation::request_token),
+                            None => Task::done(None),
+                        })
+                        .map(move |token| Message::Launch(index, token))
                 }
+                None => Task::none(),
+            },
+            Message::Launch(index, token) => match self.targets.get(index).cloned() {
+                Some((app, action)) => self.open_with_app(app, action, token),
+                None => Task::none(),
             },
         }
     }
 
-    fn view(&self) -> Element<'_, Message> {
-        log::info!("Rendering URL handler selection UI");
+    fn remember_choice(&mut self, app: &UrlHandlerApp, action: Option<&AppAction>) {
+        let choice = Choice {
+            handler: app.appid.clone(),
+            action: action.map(|action| action.id.clone()),
+            always: self.remember,
+        };
+        for key in self.choice_keys() {
+            self.store.remember(key, choice.clone());
+        }
+        if let Err(e) = self.store.save() {
+            log::error!("Failed to save choice for {}: {}", self.urls[0], e);
+        }
+    }
 
-        let apps_buttons = self.apps.iter().map(|app| {
-            let app_icon = app_icon(app, ICON_SIZE);
-            let app_name = text(truncate_with_ellipsis(&app.name, 12)).center();
+    /// The hosts or schemes of the URLs, without duplicates.
+    fn choice_keys(&self) -> Vec<ChoiceKey> {
+        let mut keys = vec![];
+        for key in self.urls.iter().map(ChoiceKey::for_url) {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+
+    fn open_with_app(
+        &self,
+        app: UrlHandlerApp,
+        action: Option<AppAction>,
+        token: Option<ActivationToken>,
+    ) -> Task<Message> {
+        let action_id = action.as_ref().map(|action| action.id.as_str());
+        let options = LaunchOptions {
+            activation_token: token.or_else(|| self.options.activation_token.clone()),
+            ..self.options.clone()
+        };
+        match app.open_urls_with_options(&self.urls, action_id, &options) {
+            Ok(_) => iced::exit(),
+            Err(e) => {
+                // TODO: display error message on UI
+                log::error!(
+                    "Failed to open URLs with '{}': {}",
+                    app.display_name(action.as_ref()),
+                    e
+                );
+                std::process::exit(e.exit_code());
+            }
+        }
+    }
 
-            let label = column![app_icon, app_name]
-                .spacing(INNER_SPACING)
-                .width(100)
-                .align_x(Alignment::Center);
+    fn subscription(&self) -> Subscription<Message> {
+        keyboard::listen().filter_map(|event| match event {
+            keyboard::Event::KeyPressed {
+                key: keyboard::Key::Named(keyboard::key::Named::Enter),
+                ..
+            } => Some(Message::OpenPreselected),
+            keyboard::Event::KeyPressed {
+                key: keyboard::Key::Named(keyboard::key::Named::Escape),
+                ..
+            } => Some(Message::Cancel),
+            _ => None,
+        })
+    }
 
-            let app_button = button(label)
-                .padding(INNER_SPACING)
-                .style(app_button_style)
-                .on_press(Message::OpenWithApp(app.clone()));
+    fn view(&self) -> Element<'_, Message> {
+        log::info!("Rendering URL handler selection UI");
 
-            tooltip(
-                app_button,
-                text(&app.name).size(TOOLTIP_FONT_SIZE),
-                tooltip::Position::FollowCursor,
-            )
-            .gap(INNER_SPACING)
-            .style(app_tooltip_style)
-            .into()
-        });
+        let apps_buttons = self
+            .targets
+            .iter()
+            .enumerate()
+            .map(|(index, (app, action))| {
+                let app_icon = app_icon(app, ICON_SIZE);
+                let label_name = action.as_ref().map_or(&app.name, |action| &action.name);
+                let app_name = text(truncate_with_ellipsis(label_name, 12)).center();
+
+                let label = column![app_icon, app_name]
+                    .spacing(INNER_SPACING)
+                    .width(100)
+                    .align_x(Alignment::Center);
+
+                let preselected = self.preselected && index == 0;
+                let app_button = button(label)
+                    .padding(INNER_SPACING)
+                    .style(move |theme, status| app_button_style(theme, status, preselected))
+                    .on_press(Message::OpenWithApp(index));
+
+                tooltip(
+                    app_button,
+                    text(app.display_name(action.as_ref())).size(TOOLTIP_FONT_SIZE),
+                    tooltip::Position::FollowCursor,
+                )
+                .gap(INNER_SPACING)
+                .style(app_tooltip_style)
+                .into()
+            });
 
         let scrollbar = scrollable::Scrollbar::new().width(2).scroller_width(5);
         let apps_container = scrollable::Scrollable::with_direction(
@@ -168,14 +357,49 @@ impl App {
             scrollable::Direction::Horizontal(scrollbar),
         );
 
-        let url_text = text(self.url.as_str())
+        let url_text = text(self.urls[0].as_str())
             .size(URL_FONT_SIZE)
             .style(text::primary)
             .align_x(Alignment::Center)
             .width(Length::Fill)
             .wrapping(text::Wrapping::Glyph);
 
-        let content: Element<_> = column![apps_container, url_text]
+        let remember = checkbox(self.remember)
+            .label(format!(
+                "Always open {} with the selected application",
+                self.choice_keys()
+                    .iter()
+                    .map(ChoiceKey::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+            .on_toggle(Message::RememberToggled)
+            .size(APP_FONT_SIZE)
+            .text_size(APP_FONT_SIZE);
+
+        let mut details = vec![];
+        if self.urls.len() > 1 {
+            details.push(format!("and {} more URLs", self.urls.len() - 1));
+        }
+        if !self.removed.is_empty() {
+            details.push(format!("Removed {}", self.removed.join(", ")));
+        }
+
+        let url_info: Element<_> = if details.is_empty() {
+            url_text.into()
+        } else {
+            let details_text = text(details.join(" · "))
+                .size(TOOLTIP_FONT_SIZE)
+                .style(text::secondary)
+                .align_x(Alignment::Center)
+                .width(Length::Fill)
+                .wrapping(text::Wrapping::Glyph);
+            column![url_text, details_text]
+                .spacing(INNER_SPACING / 2.0)
+                .into()
+        };
+
+        let content: Element<_> = column![apps_container, url_info, remember]
             .spacing(OUTER_SPACING)
             .padding(OUTER_SPACING)
             .align_x(Alignment::Center)
@@ -191,10 +415,29 @@ impl App {
     }
 
     fn theme(&self) -> Theme {
-        Theme::TokyoNight
+        self.theme.clone()
     }
 }
 
+/// Finds one of the built-in themes by name, ignoring case, spaces, dashes and underscores.
+fn find_theme(name: &str) -> Option<Theme> {
+    let normalize = |name: &str| {
+        name.chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .collect::<String>()
+            .to_lowercase()
+    };
+
+    let theme = Theme::ALL
+        .iter()
+        .find(|theme| normalize(&theme.to_string()) == normalize(name))
+        .cloned();
+    if theme.is_none() {
+        log::warn!("Unknown theme '{name}', using the default one");
+    }
+    theme
+}
+
 fn app_icon<T>(app: &UrlHandlerApp, icon_size: u16) -> Element<'_, T> {
     let length = iced::Length::from(icon_size.to_u32());
     match app.icon_path(icon_size) {
@@ -218,12 +461,19 @@ fn app_icon<T>(app: &UrlHandlerApp, icon_size: u16) -> Element<'_, T> {
     }
 }
 
-fn app_button_style(theme: &Theme, status: button::Status) -> button::Style {
+fn app_button_style(theme: &Theme, status: button::Status, preselected: bool) -> button::Style {
     let palette = theme.extended_palette();
+    let border = if preselected {
+        border::rounded(BORDER_RADIUS)
+            .color(palette.primary.weak.color)
+            .width(1)
+    } else {
+        border::rounded(BORDER_RADIUS)
+    };
     let style = button::Style {
         background: None,
         text_color: palette.secondary.base.text,
-        border: border::rounded(BORDER_RADIUS),
+        border,
         ..button::Style::default()
     };
 
@@ -1,5 +1,17 @@
+use std::{collections::HashMap, path::PathBuf};
+
 use clap::Parser;
-use kairo_core::{Url, UrlHandlerApp};
+use kairo_core::{
+    ActivationToken,
+    Config,
+    Error,
+    HandlerRegistry,
+    LaunchOptions,
+    Terminal,
+    Url,
+    UrlBatches,
+    unwrap_redirects,
+};
 
 use crate::app;
 
@@ -8,8 +20,23 @@ use crate::app;
 #[command(version, author, about, long_about = None)]
 #[command(next_line_help = true)]
 pub struct Cli {
-    /// The URL to open.
-    url: Url,
+    /// The URLs to open. The ones without a routing rule or remembered choice are opened with
+    /// the application selected in the picker.
+    #[arg(required = true)]
+    urls: Vec<Url>,
+
+    /// Opens the URLs as is, without removing tracking parameters.
+    #[arg(long, default_value_t = false)]
+    no_clean: bool,
+
+    /// Terminal command used to run terminal applications, with a {command} placeholder (e.g.
+    /// "kitty --single-instance {command}"). Detected automatically if not set.
+    #[arg(long)]
+    terminal: Option<String>,
+
+    /// Path to the configuration file. Defaults to $XDG_CONFIG_HOME/kairo/config.toml.
+    #[arg(long)]
+    config: Option<PathBuf>,
 
     /// Paths to search for desktop entries.
     #[arg(long, default_value = None, global = true)]
@@ -34,15 +61,119 @@ impl Cli {
             .filter_level(self.verbose.log_level_filter())
             .init();
 
-        let apps =
-            UrlHandlerApp::handlers_for_scheme(self.url.scheme(), None, self.search_paths.clone())?;
+        let mut config = Config::load_from(self.config.as_deref())?;
+        if self.search_paths.is_some() {
+            config.search_paths = self.search_paths.clone();
+        }
+
+        let options = LaunchOptions {
+            terminal: self
+                .terminal
+                .as_deref()
+                .or(config.terminal.as_deref())
+                .map(Terminal::from_template)
+                .transpose()?,
+            activation_token: ActivationToken::from_env(),
+            wrapper: config.wrapper.clone(),
+            ..Default::default()
+        };
+
+        let cleaner = match self.no_clean {
+            true => None,
+            false => Some(config.cleaner()?),
+        };
+        let router = config.router()?;
+        let registry = HandlerRegistry::from_config(&config);
+        let store = config.choices()?;
+        let mut handlers = HashMap::new();
+        let mut batches = UrlBatches::new();
+        let mut pending = vec![];
+        let mut removed = vec![];
+
+        for url in &self.urls {
+            let url = unwrap_redirects(url).url;
+            let url = match &cleaner {
+                Some(cleaner) => {
+                    let cleaned = cleaner.clean(&url);
+                    for param in cleaned.removed {
+                        if !removed.contains(&param) {
+                            removed.push(param);
+                        }
+                    }
+                    cleaned.url
+                }
+                None => url,
+            };
+
+            let scheme = url.scheme().to_string();
+            if !handlers.contains_key(&scheme) {
+                handlers.insert(scheme.clone(), registry.handlers_for_scheme(&scheme)?);
+            }
+            let apps = &handlers[&scheme];
+
+            if let Some(route) = router.route(&url, apps) {
+                batches.push(&route.handler(), route.action, url);
+                continue;
+            }
+
+            let always = store
+                .lookup(&url)
+                .filter(|(_, choice)| choice.always)
+                .and_then(|(_, choice)| {
+                    let app = apps.iter().find(|app| app.appid == choice.handler)?;
+                    let action = match &choice.action {
+                        Some(action) => Some(app.action(action)?),
+                        None => None,
+                    };
+                    Some((app, action))
+                });
+            match always {
+                Some((app, action)) => batches.push(app, action, url),
+                None => pending.push(url),
+            }
+        }
+
+        for batch in batches.iter() {
+            batch.open(&options)?;
+        }
+
+        let Some(first) = pending.first() else {
+            return Ok(());
+        };
+
+        // The picker offers the apps that can open all the remaining URLs
+        let apps = handlers[first.scheme()]
+            .iter()
+            .filter(|app| {
+                pending.iter().all(|url| {
+                    handlers[url.scheme()]
+                        .iter()
+                        .any(|other| other.appid == app.appid)
+                })
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if apps.is_empty() {
+            return Err(Error::NoHandlersFound(first.scheme().to_string()).into());
+        }
+
+        // Skip the picker, using the last app chosen for the first URL or the default one
+        if config.picker.no_prompt {
+            let remembered = store.lookup(first).and_then(|(_, choice)| {
+                let app = apps.iter().find(|app| app.appid == choice.handler)?;
+                Some((app, choice.action.as_deref()))
+            });
+            let (app, action) = remembered.unwrap_or((&apps[0], None));
+            app.open_urls_with_options(&pending, action, &options)?;
+            return Ok(());
+        }
 
         #[cfg(debug_assertions)]
         let debug_ui = self.debug_ui;
         #[cfg(not(debug_assertions))]
         let debug_ui = false;
 
-        app::run(self.url.clone(), apps, debug_ui)?;
+        app::run(pending, removed, apps, store, options, config, debug_ui)?;
 
         Ok(())
     }
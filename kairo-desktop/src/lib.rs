@@ -1,3 +1,4 @@
+mod activation;
 mod app;
 pub mod cli;
 
@@ -1,6 +1,9 @@
 fn main() {
     if let Err(e) = kairo_desktop::run() {
         eprintln!("Error: {}", e);
-        std::process::exit(1);
+        let code = e
+            .downcast_ref::<kairo_desktop::Error>()
+            .map(kairo_desktop::Error::exit_code);
+        std::process::exit(code.unwrap_or(1));
     }
 }
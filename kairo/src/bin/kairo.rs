@@ -3,11 +3,7 @@ fn main() {
         Ok(code) => code,
         Err(e) => {
             eprintln!("Error: {}", e);
-            let code = match e {
-                kairo_cli::Error::OpenUrl(_, status) => status.code(),
-                _ => None,
-            };
-            std::process::exit(code.unwrap_or(1));
+            std::process::exit(e.exit_code());
         }
     }
 }